@@ -0,0 +1,151 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::ServerError;
+
+/// Name of the query parameter clients can pass their token in, percent-encoded, when they can't
+/// set headers.
+pub const TOKEN_QUERY_PARAMETER: &str = "token";
+
+/// A secret clients must present to connect, either as an `Authorization: Bearer <token>`
+/// header or as a `token` query parameter on the WebSocket URL.
+///
+/// The token is never printed, [`Debug`] and [`Display`](fmt::Display) redact it.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Token(String);
+
+impl Token {
+    /// Create a token, `None` if it is empty or only whitespace.
+    pub fn new(token: &str) -> Option<Self> {
+        let token = token.trim();
+
+        (!token.is_empty()).then(|| Self(token.to_string()))
+    }
+
+    /// Compare in constant time, so that the response time doesn't leak how much of a guess is
+    /// correct.
+    fn matches(&self, candidate: &str) -> bool {
+        let expected = self.0.as_bytes();
+        let candidate = candidate.as_bytes();
+
+        expected.len() == candidate.len()
+            && expected
+                .iter()
+                .zip(candidate)
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Token(***)")
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+/// Read the tokens of a token file, one per line.
+///
+/// Blank lines and lines starting with `#` are ignored, so that several clients can be given
+/// their own key and keys can be annotated.
+pub fn read_token_file(path: &Path) -> Result<Vec<Token>, ServerError> {
+    let token_file_error = |source| ServerError::TokenFile {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let content = std::fs::read_to_string(path).map_err(token_file_error)?;
+    let tokens: Vec<Token> = content
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(Token::new)
+        .collect();
+
+    if tokens.is_empty() {
+        return Err(token_file_error(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "the file contains no token",
+        )));
+    }
+
+    Ok(tokens)
+}
+
+/// Whether a connection request presents one of the accepted tokens.
+///
+/// Always true when no token is configured.
+pub(crate) fn is_authorized(
+    tokens: &[Token],
+    authorization: Option<&str>,
+    query: Option<&str>,
+) -> bool {
+    if tokens.is_empty() {
+        return true;
+    }
+
+    let from_header = authorization.and_then(|value| {
+        let (scheme, token) = value.trim().split_once(' ')?;
+        scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+    });
+    // Percent-decoded, so that tokens with reserved characters like `&` or `+` can be passed.
+    let from_query = query.and_then(|query| {
+        form_urlencoded::parse(query.as_bytes())
+            .find_map(|(key, value)| (key == TOKEN_QUERY_PARAMETER).then_some(value))
+    });
+
+    let authorized = [from_header, from_query.as_deref()]
+        .into_iter()
+        .flatten()
+        .any(|candidate| tokens.iter().any(|token| token.matches(candidate)));
+
+    authorized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens() -> Vec<Token> {
+        ["s3cret", "a&b=c+d%"]
+            .into_iter()
+            .filter_map(Token::new)
+            .collect()
+    }
+
+    #[test]
+    fn authorized_by_header() {
+        assert!(is_authorized(&tokens(), Some("Bearer s3cret"), None));
+        assert!(is_authorized(&tokens(), Some("bearer  s3cret "), None));
+        assert!(!is_authorized(&tokens(), Some("Basic s3cret"), None));
+    }
+
+    #[test]
+    fn authorized_by_query() {
+        assert!(is_authorized(&tokens(), None, Some("token=s3cret")));
+        assert!(is_authorized(&tokens(), None, Some("x=1&token=s3cret")));
+        assert!(is_authorized(
+            &tokens(),
+            None,
+            Some("token=a%26b%3Dc%2Bd%25")
+        ));
+    }
+
+    #[test]
+    fn wrong_token() {
+        assert!(!is_authorized(&tokens(), Some("Bearer guess"), None));
+        assert!(!is_authorized(&tokens(), None, Some("token=s3cre")));
+        assert!(!is_authorized(&tokens(), None, Some("other=s3cret")));
+        assert!(!is_authorized(&tokens(), None, None));
+    }
+
+    #[test]
+    fn no_token_configured() {
+        assert!(is_authorized(&[], None, None));
+        assert!(is_authorized(&[], Some("Bearer anything"), None));
+    }
+}
@@ -0,0 +1,364 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A tensor backend the server can run on.
+///
+/// Every variant exists regardless of the enabled features so that a requested backend can be
+/// parsed and reported as "not compiled in" instead of "unknown".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// NVIDIA CUDA backend (`cuda` feature).
+    Cuda,
+    /// WebGPU backend (`wgpu` feature).
+    Wgpu,
+    /// NdArray CPU backend (`ndarray` feature).
+    NdArray,
+    /// Pure-Rust CPU backend (`flex` feature).
+    Flex,
+}
+
+impl Backend {
+    /// All backends, in the order they are preferred when none is requested.
+    pub const ALL: [Backend; 4] = [
+        Backend::Cuda,
+        Backend::Wgpu,
+        Backend::NdArray,
+        Backend::Flex,
+    ];
+
+    /// The name used to request this backend, e.g. in `BURN_SERVER_BACKEND`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Cuda => "cuda",
+            Backend::Wgpu => "wgpu",
+            Backend::NdArray => "ndarray",
+            Backend::Flex => "flex",
+        }
+    }
+
+    /// Human readable description printed at startup.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Backend::Cuda => "CUDA (GPU)",
+            Backend::Wgpu => "WebGPU (GPU)",
+            Backend::NdArray => "NdArray (CPU)",
+            Backend::Flex => "Flex (CPU)",
+        }
+    }
+
+    /// Whether the backend was compiled into this binary.
+    pub fn is_available(&self) -> bool {
+        match self {
+            Backend::Cuda => cfg!(feature = "cuda"),
+            Backend::Wgpu => cfg!(feature = "wgpu"),
+            Backend::NdArray => cfg!(feature = "ndarray"),
+            Backend::Flex => cfg!(feature = "flex"),
+        }
+    }
+
+    /// The backends compiled into this binary, in order of preference.
+    pub fn available() -> Vec<Backend> {
+        Self::ALL
+            .into_iter()
+            .filter(Backend::is_available)
+            .collect()
+    }
+
+    /// The backend used when none is requested explicitly.
+    pub fn preferred() -> Option<Backend> {
+        Self::ALL.into_iter().find(Backend::is_available)
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|backend| backend.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown backend {s:?}, expected one of: {}",
+                    Self::ALL.map(|backend| backend.name()).join(", ")
+                )
+            })
+    }
+}
+
+/// A device to run on, as given to `--device` or `BURN_SERVER_DEVICE`:
+/// - `2`: the device with this index on the backend the server runs on
+/// - `cuda:2`: the third CUDA device, which also selects the CUDA backend
+/// - `wgpu:1` or `wgpu:discrete:1`: the second discrete GPU adapter
+/// - `wgpu:integrated:0`, `wgpu:virtual:0`: an integrated or virtual GPU adapter
+/// - `wgpu:cpu`: the software adapter
+/// - `cuda`, `wgpu`, `ndarray`, `flex`: the default device of the backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceSelector {
+    /// Backend named by the selector, the one the server runs on when `None`.
+    pub backend: Option<Backend>,
+    /// Type of wgpu adapter, discrete GPUs when an index is given without one.
+    pub adapter: Option<WgpuAdapter>,
+    /// Index among the devices of the backend, or among the adapters of the type on wgpu, the
+    /// default device when `None`.
+    pub index: Option<usize>,
+}
+
+/// A type of wgpu adapter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WgpuAdapter {
+    /// A GPU with its own memory.
+    Discrete,
+    /// A GPU sharing the memory of the CPU.
+    Integrated,
+    /// A GPU exposed by a hypervisor.
+    Virtual,
+    /// A software implementation running on the CPU.
+    Cpu,
+}
+
+impl WgpuAdapter {
+    const ALL: [WgpuAdapter; 4] = [
+        WgpuAdapter::Discrete,
+        WgpuAdapter::Integrated,
+        WgpuAdapter::Virtual,
+        WgpuAdapter::Cpu,
+    ];
+
+    /// The name used to request this type of adapter, e.g. `integrated` in `wgpu:integrated:0`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WgpuAdapter::Discrete => "discrete",
+            WgpuAdapter::Integrated => "integrated",
+            WgpuAdapter::Virtual => "virtual",
+            WgpuAdapter::Cpu => "cpu",
+        }
+    }
+}
+
+impl DeviceSelector {
+    /// The device with the given index on the backend the server runs on.
+    pub fn index(index: usize) -> Self {
+        Self {
+            backend: None,
+            adapter: None,
+            index: Some(index),
+        }
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts = [
+            self.backend.map(|backend| backend.name().to_string()),
+            self.adapter.map(|adapter| adapter.name().to_string()),
+            self.index.map(|index| index.to_string()),
+        ];
+        let parts = parts.into_iter().flatten().collect::<Vec<_>>();
+        f.write_str(&parts.join(":"))
+    }
+}
+
+impl FromStr for DeviceSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_index = |index: &str| {
+            index
+                .parse::<usize>()
+                .map_err(|_| format!("expected a device index, got {index:?}"))
+        };
+        let value = s.trim().to_ascii_lowercase();
+        let parts = value.split(':').collect::<Vec<_>>();
+
+        let selector = match parts[..] {
+            [index] if index.starts_with(|c: char| c.is_ascii_digit()) => {
+                Self::index(parse_index(index)?)
+            }
+            [backend, ref rest @ ..] => {
+                let backend = backend.parse::<Backend>()?;
+                let (adapter, index) = match (backend, rest) {
+                    (_, []) => (None, None),
+                    (Backend::Wgpu, [adapter] | [adapter, _])
+                        if !adapter.starts_with(|c: char| c.is_ascii_digit()) =>
+                    {
+                        let adapter = WgpuAdapter::ALL
+                            .into_iter()
+                            .find(|candidate| candidate.name() == *adapter)
+                            .ok_or_else(|| {
+                                format!(
+                                    "unknown wgpu adapter type {adapter:?}, expected one of: {}",
+                                    WgpuAdapter::ALL.map(|adapter| adapter.name()).join(", ")
+                                )
+                            })?;
+                        let index = rest.get(1).copied().map(parse_index).transpose()?;
+                        if adapter == WgpuAdapter::Cpu && index.is_some() {
+                            return Err("the wgpu cpu adapter takes no index".to_string());
+                        }
+                        (Some(adapter), index)
+                    }
+                    (_, [index]) => (None, Some(parse_index(index)?)),
+                    _ => return Err(format!("unexpected device {s:?}")),
+                };
+                Self {
+                    backend: Some(backend),
+                    adapter,
+                    index,
+                }
+            }
+            [] => unreachable!("split always returns a part"),
+        };
+
+        Ok(selector)
+    }
+}
+
+/// The devices a server runs on, as given to `--devices` or `BURN_SERVER_DEVICES`: `all` for
+/// every device of the backend, or a comma separated list of [selectors](DeviceSelector), e.g.
+/// `0,1` or `cuda:0,cuda:2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Devices {
+    /// Every device of the backend, the discrete GPUs on wgpu.
+    All,
+    /// The listed devices, in the order they are served.
+    Selected(Vec<DeviceSelector>),
+}
+
+impl fmt::Display for Devices {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Devices::All => f.write_str("all"),
+            Devices::Selected(devices) => {
+                let devices = devices.iter().map(ToString::to_string).collect::<Vec<_>>();
+                f.write_str(&devices.join(","))
+            }
+        }
+    }
+}
+
+impl FromStr for Devices {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("all") {
+            return Ok(Devices::All);
+        }
+
+        let devices = s
+            .split(',')
+            .filter(|device| !device.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<DeviceSelector>, _>>()?;
+        if devices.is_empty() {
+            return Err("expected `all` or a comma separated list of devices".to_string());
+        }
+
+        Ok(Devices::Selected(devices))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_selectors() {
+        let selector = |backend, adapter, index| DeviceSelector {
+            backend,
+            adapter,
+            index,
+        };
+        let cases = [
+            ("2", DeviceSelector::index(2)),
+            ("cuda:2", selector(Some(Backend::Cuda), None, Some(2))),
+            ("wgpu:1", selector(Some(Backend::Wgpu), None, Some(1))),
+            (
+                "wgpu:discrete:1",
+                selector(Some(Backend::Wgpu), Some(WgpuAdapter::Discrete), Some(1)),
+            ),
+            (
+                "wgpu:integrated:0",
+                selector(Some(Backend::Wgpu), Some(WgpuAdapter::Integrated), Some(0)),
+            ),
+            (
+                "wgpu:virtual:0",
+                selector(Some(Backend::Wgpu), Some(WgpuAdapter::Virtual), Some(0)),
+            ),
+            (
+                "wgpu:cpu",
+                selector(Some(Backend::Wgpu), Some(WgpuAdapter::Cpu), None),
+            ),
+            ("cuda", selector(Some(Backend::Cuda), None, None)),
+            ("wgpu", selector(Some(Backend::Wgpu), None, None)),
+            ("ndarray", selector(Some(Backend::NdArray), None, None)),
+            ("flex", selector(Some(Backend::Flex), None, None)),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(value.parse::<DeviceSelector>(), Ok(expected), "{value}");
+            assert_eq!(expected.to_string(), value);
+        }
+        assert_eq!(
+            " CUDA:2 ".parse::<DeviceSelector>(),
+            Ok(selector(Some(Backend::Cuda), None, Some(2)))
+        );
+    }
+
+    #[test]
+    fn invalid_device_selectors_are_rejected() {
+        for value in [
+            "wgpu:cpu:1",
+            "cuda:x",
+            "a:b:c:d",
+            "x",
+            "-1",
+            "cuda:integrated:0",
+            "wgpu:gpu:0",
+            "wgpu:discrete:0:1",
+        ] {
+            assert!(value.parse::<DeviceSelector>().is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn devices() {
+        let cases = [
+            ("all", Devices::All),
+            (
+                "0,1",
+                Devices::Selected(vec![0, 1].into_iter().map(DeviceSelector::index).collect()),
+            ),
+            (
+                "cuda:0,cuda:2",
+                Devices::Selected(vec!["cuda:0".parse().unwrap(), "cuda:2".parse().unwrap()]),
+            ),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(value.parse::<Devices>(), Ok(expected.clone()), "{value}");
+            assert_eq!(expected.to_string(), value);
+        }
+        assert_eq!(" ALL ".parse::<Devices>(), Ok(Devices::All));
+        assert_eq!(
+            "0, 1,".parse::<Devices>(),
+            Ok(Devices::Selected(vec![
+                DeviceSelector::index(0),
+                DeviceSelector::index(1)
+            ]))
+        );
+    }
+
+    #[test]
+    fn invalid_devices_are_rejected() {
+        for value in ["", " , ", "0,x", "cuda:x", "all,0"] {
+            assert!(value.parse::<Devices>().is_err(), "{value}");
+        }
+    }
+}
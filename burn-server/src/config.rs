@@ -0,0 +1,797 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::auth::{read_token_file, Token};
+use crate::config_file::ConfigFile;
+use crate::logging::{parse_log_filter, LogFormat, DEFAULT_LOG_FILTER};
+use crate::placement::Placement;
+use crate::tls::TlsConfig;
+use crate::{Backend, DeviceSelector, Devices, ServerError};
+
+/// Default port of the server.
+pub const DEFAULT_PORT: u16 = 3000;
+
+/// Default time given to open connections to finish on shutdown.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default interval of the keepalive probes sent to idle clients.
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Default time a client may go without answering before it is considered gone.
+pub const DEFAULT_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Configuration of a burn-server instance.
+///
+/// [`ServerConfig::default`] matches the behavior of the `burn-server` binary without any
+/// environment variables set, and [`ServerConfig::from_env`] reads the same configuration file and
+/// environment variables as the binary.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// The TOML [configuration file](Self::from_file) the configuration was read from, if any.
+    pub config_file: Option<PathBuf>,
+    /// Port to listen on, `0` to let the OS assign a free port.
+    pub port: u16,
+    /// Address of the interface to listen on, all IPv4 interfaces by default.
+    ///
+    /// Use a loopback address (`127.0.0.1`, `::1`) to only accept local connections, e.g. through
+    /// an SSH tunnel, the address of a network interface to only accept connections on it, or
+    /// `::` to listen on all IPv6 interfaces (and IPv4 ones where the OS maps them to IPv6).
+    pub bind_address: IpAddr,
+    /// Backend to run on, the preferred compiled-in backend when `None`.
+    pub backend: Option<Backend>,
+    /// Device to run on, the default device of the backend when `None`.
+    ///
+    /// A selector naming a backend, like `cuda:2`, also selects that backend when
+    /// [`backend`](Self::backend) is `None`.
+    pub device: Option<DeviceSelector>,
+    /// Devices served by one process, each under `/device/<n>` with `n` its position in the
+    /// list, the first one also at the root. Only [`device`](Self::device) is served when `None`.
+    pub devices: Option<Devices>,
+    /// How sessions connecting to the root are placed on the [devices](Self::devices).
+    pub placement: Placement,
+    /// Limits applied to client connections.
+    pub limits: Limits,
+    /// File the bound port is written to once the server is listening, so that harnesses can
+    /// discover the port assigned by the OS.
+    pub port_file: Option<PathBuf>,
+    /// How long open connections may keep running after a shutdown is requested before they are
+    /// closed.
+    pub shutdown_timeout: Duration,
+    /// Token clients must present to connect.
+    ///
+    /// Authentication is disabled when neither a token nor a token file is configured, in which
+    /// case anyone who can reach the port can run work on the device.
+    pub token: Option<Token>,
+    /// File with the tokens clients may present, one per line, accepted in addition to
+    /// [`token`](Self::token).
+    pub token_file: Option<PathBuf>,
+    /// Accept clients that don't report their version of burn in the
+    /// [`x-burn-version`](crate::BURN_VERSION_HEADER) header, like burn's own remote client.
+    ///
+    /// Such a client built with another version of burn fails on the first operation the server
+    /// can't decode instead of on connecting, so they are rejected with HTTP 400 by default.
+    pub allow_unversioned_clients: bool,
+    /// Certificate and key to serve `wss://` with, plain `ws://` when `None`.
+    pub tls: Option<TlsConfig>,
+    /// Filter of the log records in the syntax of `RUST_LOG`, e.g. `info,burn_server=debug`.
+    pub log_filter: String,
+    /// Format of the log records written to stdout.
+    pub log_format: LogFormat,
+}
+
+/// Limits applied to client connections.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// Maximum size of a WebSocket message in bytes, bounds the size of a single tensor upload.
+    pub max_message_size: usize,
+    /// Maximum size of a single WebSocket frame in bytes.
+    pub max_frame_size: usize,
+    /// Maximum number of simultaneous client sessions, unlimited when `None`.
+    pub max_sessions: Option<usize>,
+    /// How long a new session waits for another one to close when
+    /// [`max_sessions`](Self::max_sessions) are open, before it is rejected. Zero rejects it
+    /// right away.
+    pub session_queue_timeout: Duration,
+    /// Maximum bytes of tensors a session may hold on the device, unlimited when `None`.
+    ///
+    /// Allocations are estimated from the shape and type of the tensors created on behalf of the
+    /// client. The operation exceeding the quota isn't executed, and the session fails with an
+    /// error returned to the client on its next read.
+    pub session_memory_quota: Option<u64>,
+    /// How long a session may go without any request from its client before it is closed and its
+    /// tensors freed, never when `None`.
+    pub session_idle_timeout: Option<Duration>,
+    /// Interval of the TCP keepalive probes sent to idle clients to detect peers that went away
+    /// without closing their connection, e.g. a laptop going to sleep. Zero disables them.
+    pub heartbeat_interval: Duration,
+    /// How long a client may go without answering probes or acknowledging data before its
+    /// session is closed and its tensors freed.
+    pub heartbeat_timeout: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            config_file: None,
+            port: DEFAULT_PORT,
+            bind_address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            backend: None,
+            device: None,
+            devices: None,
+            placement: Placement::default(),
+            limits: Limits::default(),
+            port_file: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            token: None,
+            token_file: None,
+            allow_unversioned_clients: false,
+            tls: None,
+            log_filter: DEFAULT_LOG_FILTER.to_string(),
+            log_format: LogFormat::default(),
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        const MB: usize = 1024 * 1024;
+
+        Self {
+            max_message_size: 64 * MB,
+            max_frame_size: 16 * MB,
+            max_sessions: None,
+            session_queue_timeout: Duration::ZERO,
+            session_memory_quota: None,
+            session_idle_timeout: None,
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            heartbeat_timeout: DEFAULT_HEARTBEAT_TIMEOUT,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Read the configuration from the environment, over the settings of the configuration file
+    /// at `BURN_SERVER_CONFIG` when it is set.
+    ///
+    /// - `BURN_SERVER_CONFIG`: TOML configuration file, see [`from_file`](Self::from_file)
+    ///   (default: none)
+    /// - `REMOTE_BACKEND_PORT`: port to listen on, `0` for a port assigned by the OS (default: 3000)
+    /// - `BURN_SERVER_PORT_FILE`: file the bound port is written to (default: none)
+    /// - `BURN_SERVER_BIND`: address of the interface to listen on (default: 0.0.0.0)
+    /// - `BURN_SERVER_BACKEND`: backend to run on (default: first compiled-in backend)
+    /// - `BURN_SERVER_DEVICE`: device to run on, e.g. `2`, `cuda:2` or `wgpu:integrated:0`
+    ///   (default: the default device of the backend)
+    /// - `BURN_SERVER_DEVICES`: devices to serve under `/device/<n>`, `all` or a list like `0,1`
+    ///   (default: only `BURN_SERVER_DEVICE`)
+    /// - `BURN_SERVER_PLACEMENT`: how sessions connecting to the root are placed on the devices,
+    ///   `sessions` or `memory-in-use` (default: sessions)
+    /// - `BURN_SERVER_SHUTDOWN_TIMEOUT`: seconds given to open connections on shutdown (default: 30)
+    /// - `BURN_SERVER_MAX_SESSIONS`: maximum number of simultaneous sessions (default: unlimited)
+    /// - `BURN_SERVER_SESSION_QUEUE_TIMEOUT`: seconds a new session waits for a free slot before
+    ///   it is rejected (default: 0)
+    /// - `BURN_SERVER_SESSION_MEMORY_QUOTA`: bytes of tensors a session may hold, with an optional
+    ///   unit, e.g. `4GiB` (default: unlimited)
+    /// - `BURN_SERVER_SESSION_IDLE_TIMEOUT`: seconds without requests before a session is closed, `0`
+    ///   for never (default: 0)
+    /// - `BURN_SERVER_HEARTBEAT_INTERVAL`: whole seconds between keepalive probes to idle clients,
+    ///   `0` to disable them (default: 15)
+    /// - `BURN_SERVER_HEARTBEAT_TIMEOUT`: seconds a client may go without answering before its
+    ///   session is closed (default: 60)
+    /// - `BURN_SERVER_TOKEN`: token clients must present (default: none)
+    /// - `BURN_SERVER_TOKEN_FILE`: file with the tokens clients may present (default: none)
+    /// - `BURN_SERVER_ALLOW_UNVERSIONED_CLIENTS`: `true` to accept clients that don't report their
+    ///   version of burn, like burn's own remote client (default: false)
+    /// - `BURN_SERVER_TLS_CERT`, `BURN_SERVER_TLS_KEY`: PEM certificate chain and private key to
+    ///   serve `wss://` with, both or neither must be set (default: none)
+    /// - `RUST_LOG`: filter of the log records (default: `info,wgpu=warn`)
+    /// - `BURN_SERVER_LOG_FORMAT`: `text` or `json` (default: text)
+    pub fn from_env() -> Result<Self, ServerError> {
+        Self::load(None)
+    }
+
+    /// Read the configuration file at `path`, or else at `BURN_SERVER_CONFIG`, overridden by the
+    /// environment like [`from_env`](Self::from_env).
+    pub fn load(path: Option<PathBuf>) -> Result<Self, ServerError> {
+        Self::load_with_env(path, |name| std::env::var_os(name))
+    }
+
+    /// Like [`load`](Self::load), with the environment variables looked up by `env` instead of
+    /// in the environment of the process.
+    pub fn load_with_env(
+        path: Option<PathBuf>,
+        env: impl Fn(&str) -> Option<OsString>,
+    ) -> Result<Self, ServerError> {
+        let path = path.or_else(|| {
+            env("BURN_SERVER_CONFIG")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from)
+        });
+        let mut config = match &path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.config_file = path;
+        config.apply_env(&env)?;
+
+        Ok(config)
+    }
+
+    /// Read the TOML configuration file at the path, without the environment.
+    ///
+    /// The file has the sections `network`, `backend`, `auth`, `limits` and `logging`, each
+    /// setting taking the values of its environment variable, e.g.
+    ///
+    /// ```toml
+    /// [network]
+    /// port = 3000
+    /// bind = "127.0.0.1"
+    ///
+    /// [backend]
+    /// devices = ["cuda:0", "cuda:2"]
+    ///
+    /// [auth]
+    /// token_file = "/etc/burn-server/tokens"
+    ///
+    /// [limits]
+    /// max_sessions = 4
+    /// session_memory_quota = "4GiB"
+    ///
+    /// [logging]
+    /// filter = "info,burn_server=debug"
+    /// format = "json"
+    /// ```
+    ///
+    /// Unknown sections and settings are rejected, and invalid values are reported with the key
+    /// of their setting, e.g. `limits.max_sessions`.
+    pub fn from_file(path: &Path) -> Result<Self, ServerError> {
+        let mut config = Self::default();
+        ConfigFile::read(path)?
+            .apply(&mut config)
+            .map_err(|err| ServerError::ConfigFile {
+                path: path.to_path_buf(),
+                reason: err.to_string(),
+            })?;
+        config.config_file = Some(path.to_path_buf());
+
+        Ok(config)
+    }
+
+    /// Override the configuration with the environment variables that are set.
+    fn apply_env(&mut self, env: &impl Fn(&str) -> Option<OsString>) -> Result<(), ServerError> {
+        let config = self;
+        // Like `std::env::var`, a value that isn't Unicode is ignored.
+        let var = |name: &str| env(name).and_then(|value| value.into_string().ok());
+
+        if let Some(port) = var("REMOTE_BACKEND_PORT") {
+            config.port = parse_port(&port)?;
+        }
+
+        if let Some(path) = env("BURN_SERVER_PORT_FILE") {
+            if !path.is_empty() {
+                config.port_file = Some(path.into());
+            }
+        }
+
+        if let Some(address) = var("BURN_SERVER_BIND") {
+            if !address.trim().is_empty() {
+                config.bind_address = parse_bind_address(&address)?;
+            }
+        }
+
+        if let Some(name) = var("BURN_SERVER_BACKEND") {
+            if !name.trim().is_empty() {
+                config.backend = Some(parse_backend(&name)?);
+            }
+        }
+
+        let device = var("BURN_SERVER_DEVICE")
+            .filter(|device| !device.trim().is_empty())
+            .map(|device| parse_device(&device))
+            .transpose()?;
+        let devices = var("BURN_SERVER_DEVICES")
+            .filter(|devices| !devices.trim().is_empty())
+            .map(|devices| parse_devices(&devices))
+            .transpose()?;
+        // A device set in the environment replaces the devices of the file, and the other way
+        // around.
+        if device.is_some() || devices.is_some() {
+            config.device = device;
+            config.devices = devices;
+        }
+
+        if let Some(placement) = var("BURN_SERVER_PLACEMENT") {
+            if !placement.trim().is_empty() {
+                config.placement = parse_placement(&placement)?;
+            }
+        }
+
+        if let Some(seconds) = var("BURN_SERVER_SHUTDOWN_TIMEOUT") {
+            if !seconds.trim().is_empty() {
+                config.shutdown_timeout = parse_shutdown_timeout(&seconds)?;
+            }
+        }
+
+        if let Some(count) = var("BURN_SERVER_MAX_SESSIONS") {
+            if !count.trim().is_empty() {
+                config.limits.max_sessions = Some(parse_max_sessions(&count)?);
+            }
+        }
+
+        if let Some(seconds) = var("BURN_SERVER_SESSION_QUEUE_TIMEOUT") {
+            if !seconds.trim().is_empty() {
+                config.limits.session_queue_timeout = parse_session_queue_timeout(&seconds)?;
+            }
+        }
+
+        if let Some(size) = var("BURN_SERVER_SESSION_MEMORY_QUOTA") {
+            if !size.trim().is_empty() {
+                config.limits.session_memory_quota = Some(parse_session_memory_quota(&size)?);
+            }
+        }
+
+        if let Some(seconds) = var("BURN_SERVER_SESSION_IDLE_TIMEOUT") {
+            if !seconds.trim().is_empty() {
+                config.limits.session_idle_timeout = parse_session_idle_timeout(&seconds)?;
+            }
+        }
+
+        if let Some(seconds) = var("BURN_SERVER_HEARTBEAT_INTERVAL") {
+            if !seconds.trim().is_empty() {
+                config.limits.heartbeat_interval = parse_heartbeat_interval(&seconds)?;
+            }
+        }
+
+        if let Some(seconds) = var("BURN_SERVER_HEARTBEAT_TIMEOUT") {
+            if !seconds.trim().is_empty() {
+                config.limits.heartbeat_timeout = parse_heartbeat_timeout(&seconds)?;
+            }
+        }
+
+        if let Some(token) = var("BURN_SERVER_TOKEN") {
+            if !token.trim().is_empty() {
+                config.token = Token::new(&token);
+            }
+        }
+
+        if let Some(path) = env("BURN_SERVER_TOKEN_FILE") {
+            if !path.is_empty() {
+                config.token_file = Some(path.into());
+            }
+        }
+
+        if let Some(allow) = var("BURN_SERVER_ALLOW_UNVERSIONED_CLIENTS") {
+            if !allow.trim().is_empty() {
+                config.allow_unversioned_clients = parse_allow_unversioned_clients(&allow)?;
+            }
+        }
+
+        let tls_cert = env("BURN_SERVER_TLS_CERT").filter(|path| !path.is_empty());
+        let tls_key = env("BURN_SERVER_TLS_KEY").filter(|path| !path.is_empty());
+        if tls_cert.is_some() || tls_key.is_some() {
+            let (cert, key) = config.tls.take().map(|tls| (tls.cert, tls.key)).unzip();
+            config.tls = tls_config(
+                tls_cert.map(PathBuf::from).or(cert),
+                tls_key.map(PathBuf::from).or(key),
+            )?;
+        }
+
+        if let Some(filter) = var("RUST_LOG") {
+            if !filter.trim().is_empty() {
+                config.log_filter = parse_log_filter(&filter)?;
+            }
+        }
+
+        if let Some(format) = var("BURN_SERVER_LOG_FORMAT") {
+            if !format.trim().is_empty() {
+                config.log_format = parse_log_format(&format)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The socket address to listen on.
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.port)
+    }
+
+    /// The tokens clients may present, empty when authentication is disabled.
+    ///
+    /// Reads the token file when one is configured.
+    pub fn tokens(&self) -> Result<Vec<Token>, ServerError> {
+        let mut tokens: Vec<Token> = self.token.iter().cloned().collect();
+        if let Some(path) = &self.token_file {
+            tokens.extend(read_token_file(path)?);
+        }
+
+        Ok(tokens)
+    }
+}
+
+impl fmt::Display for ServerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.config_file {
+            Some(path) => writeln!(f, "config_file = {}", path.display())?,
+            None => writeln!(f, "config_file = (none)")?,
+        }
+        writeln!(f, "port = {}", self.port)?;
+        writeln!(f, "bind_address = {}", self.bind_address)?;
+        match self.backend {
+            Some(backend) => writeln!(f, "backend = {backend}")?,
+            None => writeln!(f, "backend = (preferred)")?,
+        }
+        match self.device {
+            Some(device) => writeln!(f, "device = {device}")?,
+            None => writeln!(f, "device = (default)")?,
+        }
+        match &self.devices {
+            Some(devices) => writeln!(f, "devices = {devices}")?,
+            None => writeln!(f, "devices = (single device)")?,
+        }
+        writeln!(f, "placement = {}", self.placement)?;
+        writeln!(f, "max_message_size = {}", self.limits.max_message_size)?;
+        writeln!(f, "max_frame_size = {}", self.limits.max_frame_size)?;
+        match self.limits.max_sessions {
+            Some(count) => writeln!(f, "max_sessions = {count}")?,
+            None => writeln!(f, "max_sessions = (unlimited)")?,
+        }
+        writeln!(
+            f,
+            "session_queue_timeout = {}s",
+            self.limits.session_queue_timeout.as_secs_f64()
+        )?;
+        match self.limits.session_memory_quota {
+            Some(bytes) => writeln!(f, "session_memory_quota = {bytes}")?,
+            None => writeln!(f, "session_memory_quota = (unlimited)")?,
+        }
+        match self.limits.session_idle_timeout {
+            Some(timeout) => writeln!(f, "session_idle_timeout = {}s", timeout.as_secs_f64())?,
+            None => writeln!(f, "session_idle_timeout = (never)")?,
+        }
+        writeln!(
+            f,
+            "heartbeat_interval = {}s",
+            self.limits.heartbeat_interval.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "heartbeat_timeout = {}s",
+            self.limits.heartbeat_timeout.as_secs_f64()
+        )?;
+        match &self.port_file {
+            Some(path) => writeln!(f, "port_file = {}", path.display())?,
+            None => writeln!(f, "port_file = (none)")?,
+        }
+        writeln!(
+            f,
+            "shutdown_timeout = {}s",
+            self.shutdown_timeout.as_secs_f64()
+        )?;
+        match &self.token {
+            Some(token) => writeln!(f, "token = {token}")?,
+            None => writeln!(f, "token = (none)")?,
+        }
+        match &self.token_file {
+            Some(path) => writeln!(f, "token_file = {}", path.display())?,
+            None => writeln!(f, "token_file = (none)")?,
+        }
+        writeln!(
+            f,
+            "allow_unversioned_clients = {}",
+            self.allow_unversioned_clients
+        )?;
+        match &self.tls {
+            Some(tls) => writeln!(
+                f,
+                "tls = cert {}, key {}",
+                tls.cert.display(),
+                tls.key.display()
+            )?,
+            None => writeln!(f, "tls = (none)")?,
+        }
+        writeln!(f, "log_filter = {}", self.log_filter)?;
+        write!(f, "log_format = {}", self.log_format)
+    }
+}
+
+/// Parse a port number.
+pub fn parse_port(value: &str) -> Result<u16, ServerError> {
+    value
+        .trim()
+        .parse::<u16>()
+        .map_err(|source| ServerError::InvalidPort {
+            value: value.to_string(),
+            source,
+        })
+}
+
+/// Parse the address of the interface to listen on.
+///
+/// IPv6 addresses may be enclosed in brackets, e.g. `[::1]`.
+pub fn parse_bind_address(value: &str) -> Result<IpAddr, ServerError> {
+    let trimmed = value.trim();
+    let address = trimmed
+        .strip_prefix('[')
+        .and_then(|address| address.strip_suffix(']'))
+        .unwrap_or(trimmed);
+
+    address
+        .parse::<IpAddr>()
+        .map_err(|source| ServerError::InvalidBindAddress {
+            value: value.to_string(),
+            source,
+        })
+}
+
+/// Build the TLS configuration from a certificate and a key, which must be given together.
+pub fn tls_config(
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+) -> Result<Option<TlsConfig>, ServerError> {
+    match (cert, key) {
+        (Some(cert), Some(key)) => Ok(Some(TlsConfig { cert, key })),
+        (None, None) => Ok(None),
+        (Some(_), None) => Err(ServerError::Tls {
+            reason: "a certificate is set but no key, set BURN_SERVER_TLS_KEY or --tls-key"
+                .to_string(),
+        }),
+        (None, Some(_)) => Err(ServerError::Tls {
+            reason: "a key is set but no certificate, set BURN_SERVER_TLS_CERT or --tls-cert"
+                .to_string(),
+        }),
+    }
+}
+
+/// Parse a backend name.
+pub fn parse_backend(name: &str) -> Result<Backend, ServerError> {
+    name.parse().map_err(|_| ServerError::UnknownBackend {
+        name: name.to_string(),
+    })
+}
+
+/// Parse a device selector, see [`DeviceSelector`] for the accepted forms.
+pub fn parse_device(value: &str) -> Result<DeviceSelector, ServerError> {
+    value.parse().map_err(|reason| ServerError::InvalidValue {
+        name: "BURN_SERVER_DEVICE",
+        value: value.to_string(),
+        reason,
+    })
+}
+
+/// Parse the devices to serve, `all` or a comma separated list of device selectors.
+pub fn parse_devices(value: &str) -> Result<Devices, ServerError> {
+    value.parse().map_err(|reason| ServerError::InvalidValue {
+        name: "BURN_SERVER_DEVICES",
+        value: value.to_string(),
+        reason,
+    })
+}
+
+/// Parse a placement policy, `sessions` or `memory-in-use`.
+pub fn parse_placement(value: &str) -> Result<Placement, ServerError> {
+    value.parse().map_err(|reason| ServerError::InvalidValue {
+        name: "BURN_SERVER_PLACEMENT",
+        value: value.to_string(),
+        reason,
+    })
+}
+
+/// Parse a shutdown timeout in seconds, fractions allowed.
+pub fn parse_shutdown_timeout(value: &str) -> Result<Duration, ServerError> {
+    parse_seconds("BURN_SERVER_SHUTDOWN_TIMEOUT", value)
+}
+
+/// Parse a session queue timeout in seconds, fractions allowed.
+pub fn parse_session_queue_timeout(value: &str) -> Result<Duration, ServerError> {
+    parse_seconds("BURN_SERVER_SESSION_QUEUE_TIMEOUT", value)
+}
+
+/// Parse a session idle timeout in seconds, fractions allowed, `None` for `0`.
+pub fn parse_session_idle_timeout(value: &str) -> Result<Option<Duration>, ServerError> {
+    parse_seconds("BURN_SERVER_SESSION_IDLE_TIMEOUT", value)
+        .map(|timeout| Some(timeout).filter(|timeout| !timeout.is_zero()))
+}
+
+/// Parse the interval between keepalive probes in whole seconds, the unit of the kernel.
+pub fn parse_heartbeat_interval(value: &str) -> Result<Duration, ServerError> {
+    value
+        .trim()
+        .parse::<u64>()
+        .map(Duration::from_secs)
+        .map_err(|err| ServerError::InvalidValue {
+            name: "BURN_SERVER_HEARTBEAT_INTERVAL",
+            value: value.to_string(),
+            reason: err.to_string(),
+        })
+}
+
+/// Parse the heartbeat timeout in seconds, fractions allowed.
+pub fn parse_heartbeat_timeout(value: &str) -> Result<Duration, ServerError> {
+    parse_seconds("BURN_SERVER_HEARTBEAT_TIMEOUT", value)
+}
+
+/// Parse the maximum number of simultaneous sessions, at least one.
+pub fn parse_max_sessions(value: &str) -> Result<usize, ServerError> {
+    value
+        .trim()
+        .parse::<usize>()
+        .map_err(|err| err.to_string())
+        .and_then(|count| match count {
+            0 => Err("at least one session must be allowed".to_string()),
+            count => Ok(count),
+        })
+        .map_err(|reason| ServerError::InvalidValue {
+            name: "BURN_SERVER_MAX_SESSIONS",
+            value: value.to_string(),
+            reason,
+        })
+}
+
+/// Parse the memory quota of a session: a number of bytes with an optional decimal (`kB`, `MB`,
+/// `GB`, `TB`) or binary (`KiB`, `MiB`, `GiB`, `TiB`) unit, e.g. `512MiB` or `1.5GB`.
+pub fn parse_session_memory_quota(value: &str) -> Result<u64, ServerError> {
+    parse_byte_size(value).map_err(|reason| ServerError::InvalidValue {
+        name: "BURN_SERVER_SESSION_MEMORY_QUOTA",
+        value: value.to_string(),
+        reason,
+    })
+}
+
+pub(crate) fn parse_byte_size(value: &str) -> Result<u64, String> {
+    const UNITS: [(&str, f64); 9] = [
+        ("b", 1.0),
+        ("kb", 1e3),
+        ("mb", 1e6),
+        ("gb", 1e9),
+        ("tb", 1e12),
+        ("kib", 1024.0),
+        ("mib", 1024.0 * 1024.0),
+        ("gib", 1024.0 * 1024.0 * 1024.0),
+        ("tib", 1024.0 * 1024.0 * 1024.0 * 1024.0),
+    ];
+
+    let value = value.trim().to_ascii_lowercase();
+    let split = value
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let multiplier = match unit.trim() {
+        "" => 1.0,
+        unit => UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .map(|(_, multiplier)| *multiplier)
+            .ok_or_else(|| {
+                format!(
+                    "unknown unit {unit:?}, expected one of: B, kB, MB, GB, TB, KiB, MiB, GiB, TiB"
+                )
+            })?,
+    };
+    let number = number
+        .trim()
+        .parse::<f64>()
+        .map_err(|err| err.to_string())?;
+    if !number.is_finite() || number <= 0.0 {
+        return Err("expected a positive size".to_string());
+    }
+
+    // Fractions of a byte round down, a size of 0 would fail every allocation.
+    match (number * multiplier) as u64 {
+        0 => Err("the size rounds down to 0 bytes, expected at least 1 byte".to_string()),
+        bytes => Ok(bytes),
+    }
+}
+
+/// Parse whether clients that don't report their version of burn are accepted, `true` or `false`.
+pub fn parse_allow_unversioned_clients(value: &str) -> Result<bool, ServerError> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err(ServerError::InvalidValue {
+            name: "BURN_SERVER_ALLOW_UNVERSIONED_CLIENTS",
+            value: value.to_string(),
+            reason: "expected true or false".to_string(),
+        }),
+    }
+}
+
+fn parse_seconds(name: &'static str, value: &str) -> Result<Duration, ServerError> {
+    value
+        .trim()
+        .parse::<f64>()
+        .map_err(|err| err.to_string())
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).map_err(|err| err.to_string()))
+        .map_err(|reason| ServerError::InvalidValue {
+            name,
+            value: value.to_string(),
+            reason,
+        })
+}
+
+/// Parse the name of a log format.
+pub fn parse_log_format(value: &str) -> Result<LogFormat, ServerError> {
+    value.parse().map_err(|reason| ServerError::InvalidValue {
+        name: "BURN_SERVER_LOG_FORMAT",
+        value: value.to_string(),
+        reason,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    /// Load the configuration with only the environment variables `vars` set.
+    fn load_with_vars(
+        path: Option<PathBuf>,
+        vars: &[(&str, &str)],
+    ) -> Result<ServerConfig, ServerError> {
+        ServerConfig::load_with_env(path, |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.into())
+        })
+    }
+
+    /// Write a configuration file for a test, removed when dropped.
+    fn config_file(contents: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+
+        file
+    }
+
+    #[test]
+    fn token_from_env() {
+        let config = load_with_vars(None, &[("BURN_SERVER_TOKEN", "s3cret")]).unwrap();
+
+        assert_eq!(config.token, Token::new("s3cret"));
+    }
+
+    #[test]
+    fn empty_token_in_env_is_unset() {
+        let file = config_file("[auth]\ntoken = \"s3cret\"\n");
+        let path = file.path().to_path_buf();
+
+        let config = load_with_vars(Some(path), &[("BURN_SERVER_TOKEN", " ")]).unwrap();
+
+        assert_eq!(config.token, Token::new("s3cret"));
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let file = config_file(
+            "[network]\nport = 4000\n\n[limits]\nmax_sessions = 2\nsession_queue_timeout = 2\n",
+        );
+        let path = file.path().to_path_buf();
+
+        let config =
+            load_with_vars(Some(path.clone()), &[("BURN_SERVER_MAX_SESSIONS", "3")]).unwrap();
+
+        assert_eq!(config.port, 4000);
+        assert_eq!(config.limits.max_sessions, Some(3));
+        assert_eq!(config.limits.session_queue_timeout, Duration::from_secs(2));
+        assert_eq!(config.config_file, Some(path));
+    }
+
+    #[test]
+    fn byte_sizes() {
+        assert_eq!(parse_byte_size("512"), Ok(512));
+        assert_eq!(parse_byte_size("1.5kB"), Ok(1500));
+        assert_eq!(parse_byte_size(" 4 GiB "), Ok(4 << 30));
+        assert!(parse_byte_size("4XB").is_err());
+        assert!(parse_byte_size("-1MiB").is_err());
+    }
+
+    #[test]
+    fn byte_sizes_rounding_down_to_zero_are_rejected() {
+        assert!(parse_byte_size("0.5B").is_err());
+        assert!(parse_byte_size("0.0001KiB").is_err());
+        assert!(parse_session_memory_quota("0").is_err());
+    }
+}
@@ -0,0 +1,373 @@
+//! The TOML configuration file of burn-server, given with `--config` or `BURN_SERVER_CONFIG`.
+//!
+//! Every setting is optional and takes the values of the matching environment variable, numbers
+//! included, e.g. `session_memory_quota = "4GiB"` or `session_queue_timeout = 2.5`:
+//!
+//! ```toml
+//! [network]
+//! port = 3000
+//! bind = "0.0.0.0"
+//! port_file = "/run/burn-server.port"
+//! shutdown_timeout = 30
+//! heartbeat_interval = 15
+//! heartbeat_timeout = 60
+//! tls_cert = "/etc/burn-server/cert.pem"
+//! tls_key = "/etc/burn-server/key.pem"
+//!
+//! [backend]
+//! name = "cuda"
+//! device = "cuda:0"          # or devices = "all", devices = ["cuda:0", "cuda:2"]
+//! placement = "sessions"
+//!
+//! [auth]
+//! token_file = "/etc/burn-server/tokens"
+//! allow_unversioned_clients = false
+//!
+//! [limits]
+//! max_message_size = "64MiB"
+//! max_frame_size = "16MiB"
+//! max_sessions = 4
+//! session_queue_timeout = 30
+//! session_memory_quota = "4GiB"
+//! session_idle_timeout = 600
+//!
+//! [logging]
+//! filter = "info,wgpu=warn"
+//! format = "json"
+//! ```
+//!
+//! Environment variables and flags override the settings of the file.
+
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::auth::Token;
+use crate::config::{
+    parse_allow_unversioned_clients, parse_backend, parse_bind_address, parse_byte_size,
+    parse_device, parse_devices, parse_heartbeat_interval, parse_heartbeat_timeout,
+    parse_log_format, parse_max_sessions, parse_placement, parse_port, parse_session_idle_timeout,
+    parse_session_memory_quota, parse_session_queue_timeout, parse_shutdown_timeout, tls_config,
+    ServerConfig,
+};
+use crate::logging::parse_log_filter;
+use crate::ServerError;
+
+/// The settings of a configuration file, by section.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    network: NetworkSection,
+    backend: BackendSection,
+    auth: AuthSection,
+    limits: LimitsSection,
+    logging: LoggingSection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct NetworkSection {
+    port: Option<Value>,
+    bind: Option<Value>,
+    port_file: Option<PathBuf>,
+    shutdown_timeout: Option<Value>,
+    heartbeat_interval: Option<Value>,
+    heartbeat_timeout: Option<Value>,
+    tls_cert: Option<PathBuf>,
+    tls_key: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BackendSection {
+    name: Option<Value>,
+    device: Option<Value>,
+    devices: Option<Value>,
+    placement: Option<Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AuthSection {
+    token: Option<String>,
+    token_file: Option<PathBuf>,
+    allow_unversioned_clients: Option<Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LimitsSection {
+    max_message_size: Option<Value>,
+    max_frame_size: Option<Value>,
+    max_sessions: Option<Value>,
+    session_queue_timeout: Option<Value>,
+    session_memory_quota: Option<Value>,
+    session_idle_timeout: Option<Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LoggingSection {
+    filter: Option<Value>,
+    format: Option<Value>,
+}
+
+/// A setting as written in the file, parsed like the value of its environment variable.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Value {
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    /// A list, joined with commas.
+    List(Vec<String>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Boolean(value) => write!(f, "{value}"),
+            Value::Integer(value) => write!(f, "{value}"),
+            Value::Float(value) => write!(f, "{value}"),
+            Value::String(value) => f.write_str(value),
+            Value::List(values) => f.write_str(&values.join(",")),
+        }
+    }
+}
+
+impl ConfigFile {
+    /// Read the configuration file at the path.
+    pub fn read(path: &Path) -> Result<Self, ServerError> {
+        let invalid = |reason: String| ServerError::ConfigFile {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let contents = std::fs::read_to_string(path).map_err(|err| invalid(err.to_string()))?;
+
+        toml::from_str(&contents).map_err(|err| invalid(err.to_string().trim_end().to_string()))
+    }
+
+    /// Override the configuration with the settings of the file.
+    ///
+    /// Invalid values are reported with the key of the setting in the file, e.g.
+    /// `limits.max_sessions`.
+    pub fn apply(self, config: &mut ServerConfig) -> Result<(), ServerError> {
+        let ConfigFile {
+            network,
+            backend,
+            auth,
+            limits,
+            logging,
+        } = self;
+
+        if let Some(port) = network.port {
+            config.port = parse_port(&port.to_string())?;
+        }
+        if let Some(address) = network.bind {
+            config.bind_address = parse_bind_address(&address.to_string())?;
+        }
+        if let Some(path) = network.port_file {
+            config.port_file = Some(path);
+        }
+        if let Some(seconds) = network.shutdown_timeout {
+            config.shutdown_timeout = parse_shutdown_timeout(&seconds.to_string())
+                .map_err(setting("network.shutdown_timeout"))?;
+        }
+        if let Some(seconds) = network.heartbeat_interval {
+            config.limits.heartbeat_interval = parse_heartbeat_interval(&seconds.to_string())
+                .map_err(setting("network.heartbeat_interval"))?;
+        }
+        if let Some(seconds) = network.heartbeat_timeout {
+            config.limits.heartbeat_timeout = parse_heartbeat_timeout(&seconds.to_string())
+                .map_err(setting("network.heartbeat_timeout"))?;
+        }
+        if network.tls_cert.is_some() || network.tls_key.is_some() {
+            config.tls = tls_config(network.tls_cert, network.tls_key)?;
+        }
+
+        if let Some(name) = backend.name {
+            config.backend = Some(parse_backend(&name.to_string())?);
+        }
+        if let Some(device) = backend.device {
+            config.device =
+                Some(parse_device(&device.to_string()).map_err(setting("backend.device"))?);
+        }
+        if let Some(devices) = backend.devices {
+            config.devices =
+                Some(parse_devices(&devices.to_string()).map_err(setting("backend.devices"))?);
+        }
+        if let Some(placement) = backend.placement {
+            config.placement =
+                parse_placement(&placement.to_string()).map_err(setting("backend.placement"))?;
+        }
+
+        if let Some(token) = auth.token {
+            config.token = Token::new(&token);
+        }
+        if let Some(path) = auth.token_file {
+            config.token_file = Some(path);
+        }
+        if let Some(allow) = auth.allow_unversioned_clients {
+            config.allow_unversioned_clients = parse_allow_unversioned_clients(&allow.to_string())
+                .map_err(setting("auth.allow_unversioned_clients"))?;
+        }
+
+        if let Some(size) = limits.max_message_size {
+            config.limits.max_message_size = parse_size("limits.max_message_size", &size)?;
+        }
+        if let Some(size) = limits.max_frame_size {
+            config.limits.max_frame_size = parse_size("limits.max_frame_size", &size)?;
+        }
+        if let Some(count) = limits.max_sessions {
+            config.limits.max_sessions = Some(
+                parse_max_sessions(&count.to_string()).map_err(setting("limits.max_sessions"))?,
+            );
+        }
+        if let Some(seconds) = limits.session_queue_timeout {
+            config.limits.session_queue_timeout = parse_session_queue_timeout(&seconds.to_string())
+                .map_err(setting("limits.session_queue_timeout"))?;
+        }
+        if let Some(size) = limits.session_memory_quota {
+            config.limits.session_memory_quota = Some(
+                parse_session_memory_quota(&size.to_string())
+                    .map_err(setting("limits.session_memory_quota"))?,
+            );
+        }
+        if let Some(seconds) = limits.session_idle_timeout {
+            config.limits.session_idle_timeout = parse_session_idle_timeout(&seconds.to_string())
+                .map_err(setting("limits.session_idle_timeout"))?;
+        }
+
+        if let Some(filter) = logging.filter {
+            config.log_filter =
+                parse_log_filter(&filter.to_string()).map_err(setting("logging.filter"))?;
+        }
+        if let Some(format) = logging.format {
+            config.log_format =
+                parse_log_format(&format.to_string()).map_err(setting("logging.format"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Report an invalid value with the key of its setting in the file instead of its environment
+/// variable.
+fn setting(key: &'static str) -> impl FnOnce(ServerError) -> ServerError {
+    move |err| match err {
+        ServerError::InvalidValue { value, reason, .. } => ServerError::InvalidValue {
+            name: key,
+            value,
+            reason,
+        },
+        err => err,
+    }
+}
+
+/// Parse a size in bytes with an optional unit, like the memory quota of a session.
+fn parse_size(key: &'static str, value: &Value) -> Result<usize, ServerError> {
+    let value = value.to_string();
+    parse_byte_size(&value)
+        .and_then(|bytes| usize::try_from(bytes).map_err(|err| err.to_string()))
+        .map_err(|reason| ServerError::InvalidValue {
+            name: key,
+            value,
+            reason,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DeviceSelector, Devices, LogFormat};
+    use std::time::Duration;
+
+    /// The configuration of a file with the contents, without the environment.
+    fn parse(contents: &str) -> Result<ServerConfig, String> {
+        let file = toml::from_str::<ConfigFile>(contents).map_err(|err| err.to_string())?;
+        let mut config = ServerConfig::default();
+        file.apply(&mut config).map_err(|err| err.to_string())?;
+
+        Ok(config)
+    }
+
+    #[test]
+    fn every_section() {
+        let config = parse(
+            r#"
+            [network]
+            port = 4000
+            bind = "127.0.0.1"
+            shutdown_timeout = 2.5
+
+            [backend]
+            devices = ["cuda:0", "cuda:2"]
+            placement = "memory-in-use"
+
+            [auth]
+            token = "s3cret"
+            allow_unversioned_clients = true
+
+            [limits]
+            max_message_size = "64MiB"
+            max_sessions = 4
+            session_memory_quota = "4GiB"
+            session_idle_timeout = 600
+
+            [logging]
+            format = "json"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.port, 4000);
+        assert_eq!(config.bind_address.to_string(), "127.0.0.1");
+        assert_eq!(config.shutdown_timeout, Duration::from_millis(2500));
+        assert_eq!(
+            config.devices,
+            Some(Devices::Selected(vec![
+                "cuda:0".parse::<DeviceSelector>().unwrap(),
+                "cuda:2".parse().unwrap(),
+            ]))
+        );
+        assert_eq!(config.placement, crate::Placement::MemoryInUse);
+        assert_eq!(config.token, Token::new("s3cret"));
+        assert!(config.allow_unversioned_clients);
+        assert_eq!(config.limits.max_message_size, 64 << 20);
+        assert_eq!(config.limits.max_sessions, Some(4));
+        assert_eq!(config.limits.session_memory_quota, Some(4 << 30));
+        assert_eq!(
+            config.limits.session_idle_timeout,
+            Some(Duration::from_secs(600))
+        );
+        assert_eq!(config.log_format, LogFormat::Json);
+    }
+
+    #[test]
+    fn empty_file_keeps_the_defaults() {
+        let config = parse("").unwrap();
+
+        assert_eq!(config.port, ServerConfig::default().port);
+        assert_eq!(config.limits.max_sessions, None);
+        assert_eq!(config.devices, None);
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        let err = parse("[limits]\nmax_session = 4\n").unwrap_err();
+        assert!(err.contains("unknown field `max_session`"), "{err}");
+
+        let err = parse("[limit]\nmax_sessions = 4\n").unwrap_err();
+        assert!(err.contains("unknown field `limit`"), "{err}");
+    }
+
+    #[test]
+    fn invalid_values_are_reported_by_key() {
+        let err = parse("[limits]\nmax_sessions = \"many\"\n").unwrap_err();
+        assert!(err.starts_with("Invalid limits.max_sessions"), "{err}");
+
+        let err = parse("[backend]\ndevice = \"cuda:x\"\n").unwrap_err();
+        assert!(err.starts_with("Invalid backend.device"), "{err}");
+    }
+}
@@ -0,0 +1,369 @@
+use burn::backend::ir::BackendIr;
+use burn::tensor::{backend::Backend as BackendOps, ElementConversion, Tensor};
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+use crate::backend::{DeviceSelector, Devices, WgpuAdapter};
+use crate::{Backend, ServerError};
+
+/// A task generic over the backend, run on the devices created by [`dispatch`].
+pub(crate) trait DeviceTask {
+    type Output;
+
+    fn run<B: BackendIr>(self, backend: Backend, devices: Vec<B::Device>) -> Self::Output;
+}
+
+/// Create the selected devices on the backend and run the task with them.
+///
+/// The task gets the default device of the backend when no device is selected.
+pub(crate) fn dispatch<T: DeviceTask>(
+    backend: Backend,
+    devices: &[DeviceSelector],
+    task: T,
+) -> Result<T::Output, ServerError> {
+    fn create<D: Default>(
+        devices: &[DeviceSelector],
+        create: impl Fn(&DeviceSelector) -> D,
+    ) -> Vec<D> {
+        match devices {
+            [] => vec![D::default()],
+            devices => devices.iter().map(create).collect(),
+        }
+    }
+
+    match backend {
+        #[cfg(feature = "cuda")]
+        Backend::Cuda => {
+            let devices = create(devices, |device| match device.index {
+                Some(index) => burn::backend::cuda::CudaDevice::new(index),
+                None => Default::default(),
+            });
+            Ok(task.run::<burn::backend::Cuda>(backend, devices))
+        }
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => {
+            use burn::backend::wgpu::WgpuDevice;
+
+            let devices = create(devices, |device| match (device.adapter, device.index) {
+                (None, None) => Default::default(),
+                (None | Some(WgpuAdapter::Discrete), index) => {
+                    WgpuDevice::DiscreteGpu(index.unwrap_or(0))
+                }
+                (Some(WgpuAdapter::Integrated), index) => {
+                    WgpuDevice::IntegratedGpu(index.unwrap_or(0))
+                }
+                (Some(WgpuAdapter::Virtual), index) => WgpuDevice::VirtualGpu(index.unwrap_or(0)),
+                (Some(WgpuAdapter::Cpu), _) => WgpuDevice::Cpu,
+            });
+            Ok(task.run::<burn::backend::Wgpu>(backend, devices))
+        }
+        #[cfg(feature = "ndarray")]
+        Backend::NdArray => Ok(
+            task.run::<burn::backend::NdArray>(backend, create(devices, |_| Default::default()))
+        ),
+        #[cfg(feature = "flex")]
+        Backend::Flex => {
+            Ok(task.run::<burn::backend::Flex>(backend, create(devices, |_| Default::default())))
+        }
+        #[allow(unreachable_patterns)]
+        backend => Err(ServerError::NoBackend {
+            requested: Some(backend),
+        }),
+    }
+}
+
+/// Resolve the devices to serve on the backend, checking that they exist.
+///
+/// `all` resolves to every device the backend can select by index, the discrete GPUs on wgpu.
+pub(crate) fn resolve_devices(
+    backend: Backend,
+    devices: &Devices,
+) -> Result<Vec<DeviceSelector>, ServerError> {
+    let selected = match devices {
+        Devices::All => {
+            let found = catch_first_panic(|| count(backend, None))
+                .map_err(|reason| ServerError::DeviceInit { backend, reason })?;
+            if found == 0 {
+                return Err(ServerError::DeviceNotFound {
+                    device: DeviceSelector {
+                        backend: Some(backend),
+                        adapter: (backend == Backend::Wgpu).then_some(WgpuAdapter::Discrete),
+                        index: Some(0),
+                    },
+                    found,
+                });
+            }
+            (0..found).map(DeviceSelector::index).collect()
+        }
+        Devices::Selected(devices) => devices.clone(),
+    };
+
+    let mut resolved = Vec::<DeviceSelector>::with_capacity(selected.len());
+    for device in selected {
+        // Without an index, the selector names the first device of its kind.
+        let device = DeviceSelector {
+            index: device
+                .index
+                .or((device.adapter != Some(WgpuAdapter::Cpu)).then_some(0)),
+            ..device
+        };
+        let device = check_selector(backend, device)?;
+        if resolved.contains(&device) {
+            return Err(ServerError::InvalidValue {
+                name: "devices",
+                value: devices.to_string(),
+                reason: format!("{device} is listed twice"),
+            });
+        }
+        resolved.push(device);
+    }
+
+    Ok(resolved)
+}
+
+/// Check that the selected device exists on the backend the server runs on.
+///
+/// Returns the selector with the backend filled in, and the adapter type on wgpu when an index
+/// is given, so that logs and errors name the device unambiguously.
+pub(crate) fn check_selector(
+    backend: Backend,
+    device: DeviceSelector,
+) -> Result<DeviceSelector, ServerError> {
+    let invalid = |reason: String| ServerError::InvalidValue {
+        name: "device",
+        value: device.to_string(),
+        reason,
+    };
+    if let Some(requested) = device.backend.filter(|requested| *requested != backend) {
+        return Err(invalid(format!(
+            "the device is on the {requested} backend but the server runs on {backend}"
+        )));
+    }
+
+    let adapter = match (backend, device.adapter) {
+        (Backend::Wgpu, None) if device.index.is_some() => Some(WgpuAdapter::Discrete),
+        (Backend::Wgpu, adapter) => adapter,
+        (_, None) => None,
+        (_, Some(_)) => {
+            return Err(invalid(format!(
+                "adapter types only exist on wgpu, the server runs on {backend}"
+            )))
+        }
+    };
+    let device = DeviceSelector {
+        backend: Some(backend),
+        adapter,
+        index: device.index,
+    };
+
+    // An adapter type without an index selects the first adapter of the type.
+    if device.index.is_some() || device.adapter.is_some() {
+        // Drivers panic when they can't be loaded, reported like a failed initialization.
+        let found = catch_first_panic(|| count(backend, adapter))
+            .map_err(|reason| ServerError::DeviceInit { backend, reason })?;
+        if device.index.unwrap_or(0) >= found {
+            return Err(ServerError::DeviceNotFound { device, found });
+        }
+    }
+
+    Ok(device)
+}
+
+/// Number of devices the backend can select by index, of the given adapter type on wgpu.
+fn count(backend: Backend, adapter: Option<WgpuAdapter>) -> usize {
+    match backend {
+        #[cfg(feature = "cuda")]
+        Backend::Cuda => {
+            use cubecl::Runtime;
+
+            cubecl::cuda::CudaRuntime::enumerate_devices(0, &()).len()
+        }
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => {
+            use cubecl::wgpu::{AutoGraphicsApi, GraphicsApi, WgpuRuntime};
+            use cubecl::Runtime;
+
+            let type_id = match adapter.unwrap_or(WgpuAdapter::Discrete) {
+                WgpuAdapter::Discrete => 0,
+                WgpuAdapter::Integrated => 1,
+                WgpuAdapter::Virtual => 2,
+                WgpuAdapter::Cpu => 3,
+            };
+            let devices = WgpuRuntime::enumerate_all_devices(&AutoGraphicsApi::backend());
+            let of_type = |type_id| {
+                devices
+                    .iter()
+                    .filter(|device| device.type_id == type_id)
+                    .count()
+            };
+            // Adapters of an unknown type are picked by index when too few of the requested type
+            // exist, so they count as well.
+            of_type(type_id).max(of_type(4))
+        }
+        _ => {
+            let _ = adapter;
+            1
+        }
+    }
+}
+
+/// Initialize the device by running a tiny operation on it.
+///
+/// Backends initialize devices lazily and panic when it fails, often on one of their own
+/// threads, so the first panic message is returned as the reason.
+pub fn init<B: BackendOps>(device: &B::Device) -> Result<(), String> {
+    catch_first_panic(|| Tensor::<B, 1>::ones([1], device).into_data()).map(|_| ())
+}
+
+/// Held while the panic hook is replaced by [`catch_first_panic`], so that servers started
+/// concurrently in one process don't capture each other's panics or restore the wrong hook.
+static PANIC_HOOK: Mutex<()> = Mutex::new(());
+
+type PanicHook = Box<dyn Fn(&panic::PanicHookInfo<'_>) + Send + Sync>;
+
+/// Restores the panic hook replaced by [`catch_first_panic`] when dropped.
+struct RestoreHook(Option<PanicHook>);
+
+impl Drop for RestoreHook {
+    fn drop(&mut self) {
+        if let Some(hook) = self.0.take() {
+            panic::set_hook(hook);
+        }
+    }
+}
+
+/// Run the function, returning the message of the first panic, on any thread, if it panics.
+///
+/// The panic hook is silenced meanwhile to keep backtraces out of the server logs.
+fn catch_first_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    let _replacing = PANIC_HOOK.lock().unwrap_or_else(|err| err.into_inner());
+    let first_panic = Arc::new(Mutex::new(None::<String>));
+    let _restore = RestoreHook(Some(panic::take_hook()));
+    panic::set_hook(Box::new({
+        let first_panic = first_panic.clone();
+        move |info| {
+            let mut first_panic = first_panic.lock().unwrap_or_else(|err| err.into_inner());
+            if first_panic.is_none() {
+                *first_panic = Some(panic_message(info.payload()));
+            }
+        }
+    }));
+
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        first_panic
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+            .unwrap_or_else(|| panic_message(payload.as_ref()))
+    })
+}
+
+/// Run a tiny operation on the device and read its result back, to check that the device is
+/// still able to run work.
+///
+/// Unlike [`init`], the panic hook is left alone so that this can run while the server is
+/// serving clients.
+pub(crate) fn check<B: BackendOps>(device: &B::Device) -> Result<(), String> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        Tensor::<B, 1>::ones([1], device)
+            .add_scalar(1.0)
+            .into_scalar()
+            .elem::<f32>()
+    }));
+
+    match result {
+        Ok(2.0) => Ok(()),
+        Ok(value) => Err(format!("1 + 1 returned {value}")),
+        Err(payload) => Err(panic_message(payload.as_ref())),
+    }
+}
+
+/// Memory of a device, in bytes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DeviceMemory {
+    /// Memory used by tensors.
+    pub in_use: u64,
+    /// Memory reserved by the memory pools, including free space.
+    pub reserved: u64,
+}
+
+/// The memory of the device, for backends that report it.
+pub(crate) fn memory<D: Any>(device: &D) -> Option<DeviceMemory> {
+    let device = device as &dyn Any;
+
+    #[cfg(feature = "cuda")]
+    if let Some(device) = device.downcast_ref::<burn::backend::cuda::CudaDevice>() {
+        use cubecl::Runtime;
+
+        let usage = cubecl::cuda::CudaRuntime::client(device)
+            .memory_usage()
+            .ok()?;
+        return Some(DeviceMemory {
+            in_use: usage.bytes_in_use,
+            reserved: usage.bytes_reserved,
+        });
+    }
+
+    #[cfg(feature = "wgpu")]
+    if let Some(device) = device.downcast_ref::<burn::backend::wgpu::WgpuDevice>() {
+        use cubecl::Runtime;
+
+        let usage = cubecl::wgpu::WgpuRuntime::client(device)
+            .memory_usage()
+            .ok()?;
+        return Some(DeviceMemory {
+            in_use: usage.bytes_in_use,
+            reserved: usage.bytes_reserved,
+        });
+    }
+
+    let _ = device;
+    None
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn devices_listed_twice_are_rejected() {
+        // An index and a backend without one both name the first device.
+        let devices = "0,ndarray".parse::<Devices>().unwrap();
+
+        let Err(ServerError::InvalidValue { name, reason, .. }) =
+            resolve_devices(Backend::NdArray, &devices)
+        else {
+            panic!("the device should be rejected");
+        };
+        assert_eq!(name, "devices");
+        assert_eq!(reason, "ndarray:0 is listed twice");
+        assert!(resolve_devices(Backend::NdArray, &"ndarray".parse().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn catch_first_panic_returns_the_value() {
+        assert_eq!(catch_first_panic(|| 2), Ok(2));
+    }
+
+    #[test]
+    fn concurrent_catches_get_their_own_panic() {
+        let threads = (0..8)
+            .map(|i| std::thread::spawn(move || catch_first_panic::<()>(|| panic!("panic {i}"))))
+            .collect::<Vec<_>>();
+
+        for (i, thread) in threads.into_iter().enumerate() {
+            assert_eq!(thread.join().unwrap(), Err(format!("panic {i}")));
+        }
+    }
+}
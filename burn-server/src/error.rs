@@ -0,0 +1,247 @@
+use std::fmt;
+use std::net::{AddrParseError, SocketAddr};
+use std::num::ParseIntError;
+use std::path::PathBuf;
+
+use crate::{Backend, DeviceSelector};
+
+/// Errors preventing the server from starting or running.
+#[derive(Debug)]
+pub enum ServerError {
+    /// The configured port is not a valid port number.
+    InvalidPort {
+        /// The configured value.
+        value: String,
+        /// Why it could not be parsed.
+        source: ParseIntError,
+    },
+    /// The configured bind address is not an IP address.
+    InvalidBindAddress {
+        /// The configured value.
+        value: String,
+        /// Why it could not be parsed.
+        source: AddrParseError,
+    },
+    /// A configured setting has an invalid value.
+    InvalidValue {
+        /// The name of the setting, as an environment variable or a key of the configuration
+        /// file.
+        name: &'static str,
+        /// The configured value.
+        value: String,
+        /// Why the value is invalid.
+        reason: String,
+    },
+    /// Another process is already listening on the address.
+    PortInUse {
+        /// The address the server tried to listen on.
+        address: SocketAddr,
+    },
+    /// The server could not listen on the address for another reason.
+    Bind {
+        /// The address the server tried to listen on.
+        address: SocketAddr,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+    /// The bound port could not be written to the port file.
+    PortFile {
+        /// The path of the port file.
+        path: PathBuf,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+    /// The configuration file could not be read or has an invalid setting.
+    ConfigFile {
+        /// The path of the configuration file.
+        path: PathBuf,
+        /// Why the file is invalid.
+        reason: String,
+    },
+    /// The token file could not be read or contains no token.
+    TokenFile {
+        /// The path of the token file.
+        path: PathBuf,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+    /// The TLS certificate or key could not be loaded.
+    Tls {
+        /// Why they could not be loaded.
+        reason: String,
+    },
+    /// The requested backend name is not a known backend.
+    UnknownBackend {
+        /// The requested name.
+        name: String,
+    },
+    /// The requested backend was not compiled in, or no backend was compiled in at all.
+    NoBackend {
+        /// The requested backend, `None` when no backend was requested.
+        requested: Option<Backend>,
+    },
+    /// The selected device does not exist on this host.
+    DeviceNotFound {
+        /// The selected device.
+        device: DeviceSelector,
+        /// How many devices of its kind exist.
+        found: usize,
+    },
+    /// The backend failed to initialize the device.
+    DeviceInit {
+        /// The backend of the device.
+        backend: Backend,
+        /// Why the device failed to initialize.
+        reason: String,
+    },
+    /// A health check of a running server failed.
+    Unhealthy {
+        /// The URL of the probed endpoint.
+        url: String,
+        /// Why the check failed.
+        reason: String,
+    },
+    /// An I/O error happened while serving clients.
+    Io(std::io::Error),
+}
+
+impl ServerError {
+    /// The process exit code reported by the `burn-server` binary for this error.
+    ///
+    /// Codes follow `sysexits.h` so that supervisors can tell configuration errors apart from
+    /// transient ones.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // EX_CONFIG
+            ServerError::InvalidPort { .. }
+            | ServerError::InvalidBindAddress { .. }
+            | ServerError::InvalidValue { .. }
+            | ServerError::ConfigFile { .. }
+            | ServerError::TokenFile { .. }
+            | ServerError::Tls { .. }
+            | ServerError::UnknownBackend { .. } => 78,
+            // EX_TEMPFAIL
+            ServerError::PortInUse { .. } => 75,
+            // EX_OSERR
+            ServerError::Bind { .. } => 71,
+            // EX_UNAVAILABLE
+            ServerError::NoBackend { .. } | ServerError::DeviceNotFound { .. } => 69,
+            // EX_SOFTWARE
+            ServerError::DeviceInit { .. } => 70,
+            // EX_CANTCREAT
+            ServerError::PortFile { .. } => 73,
+            // EX_IOERR
+            ServerError::Io(_) => 74,
+            // What container health checks expect from an unhealthy service.
+            ServerError::Unhealthy { .. } => 1,
+        }
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::InvalidPort { value, source } => {
+                write!(f, "Invalid port, got {value:?} with error {source}")
+            }
+            ServerError::InvalidBindAddress { value, source } => write!(
+                f,
+                "Invalid bind address, got {value:?} with error {source}, expected an IPv4 or IPv6 address"
+            ),
+            ServerError::InvalidValue {
+                name,
+                value,
+                reason,
+            } => write!(f, "Invalid {name}, got {value:?}: {reason}"),
+            ServerError::PortInUse { address } => {
+                write!(f, "Can't listen on {address}, the port is already in use")
+            }
+            ServerError::Bind { address, source }
+                if source.kind() == std::io::ErrorKind::AddrNotAvailable =>
+            {
+                write!(
+                    f,
+                    "Can't listen on {address}, the address is not assigned to any interface of this host: {source}"
+                )
+            }
+            ServerError::Bind { address, source } => {
+                write!(f, "Can't listen on {address}: {source}")
+            }
+            ServerError::PortFile { path, source } => {
+                write!(f, "Can't write the port file {}: {source}", path.display())
+            }
+            ServerError::ConfigFile { path, reason } => {
+                write!(f, "Invalid configuration file {}: {reason}", path.display())
+            }
+            ServerError::TokenFile { path, source } => {
+                write!(f, "Can't read the token file {}: {source}", path.display())
+            }
+            ServerError::Tls { reason } => write!(f, "Invalid TLS configuration, {reason}"),
+            ServerError::UnknownBackend { name } => write!(
+                f,
+                "Unknown backend {name:?}, expected one of: {}",
+                Backend::ALL.map(|backend| backend.name()).join(", ")
+            ),
+            ServerError::NoBackend { requested } => {
+                let available = Backend::available();
+                match requested {
+                    Some(backend) => write!(
+                        f,
+                        "Backend {backend} is not compiled into this binary, available backends: [{}]",
+                        available.iter().map(Backend::name).collect::<Vec<_>>().join(", ")
+                    ),
+                    None => write!(
+                        f,
+                        "No backend selected, rebuild with one of the backend features"
+                    ),
+                }
+            }
+            ServerError::DeviceNotFound { device, found } => {
+                let kind = match (device.backend, device.adapter) {
+                    (Some(Backend::Cuda), _) => "CUDA device".to_string(),
+                    (Some(Backend::Wgpu), Some(adapter)) => {
+                        format!("{} wgpu adapter", adapter.name())
+                    }
+                    (Some(backend), _) => {
+                        return write!(
+                            f,
+                            "Device {device} not found, the {backend} backend has a single device, {backend}:0"
+                        );
+                    }
+                    (None, _) => "device".to_string(),
+                };
+                let plural = if *found == 1 { "" } else { "s" };
+                write!(f, "Device {device} not found, this host has {found} {kind}{plural}")?;
+                if *found > 1 {
+                    write!(f, " numbered from 0")?;
+                }
+                Ok(())
+            }
+            ServerError::DeviceInit { backend, reason } => {
+                write!(f, "Failed to initialize the {backend} device: {reason}")
+            }
+            ServerError::Unhealthy { url, reason } => write!(f, "Unhealthy, {url}: {reason}"),
+            ServerError::Io(err) => write!(f, "Server error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ServerError::InvalidPort { source, .. } => Some(source),
+            ServerError::InvalidBindAddress { source, .. } => Some(source),
+            ServerError::Bind { source, .. } => Some(source),
+            ServerError::PortFile { source, .. } => Some(source),
+            ServerError::TokenFile { source, .. } => Some(source),
+            ServerError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ServerError {
+    fn from(err: std::io::Error) -> Self {
+        ServerError::Io(err)
+    }
+}
@@ -0,0 +1,162 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use burn_communication::util::os_shutdown_signal;
+use tokio::runtime::Handle;
+use tokio_util::sync::CancellationToken;
+
+use crate::reload::Reloader;
+use crate::{ReloadReport, ServerConfig, ServerError};
+
+/// A server running on a background thread, returned by [`spawn`](crate::spawn).
+pub struct ServerHandle {
+    local_addr: SocketAddr,
+    secure: bool,
+    thread: JoinHandle<Result<(), ServerError>>,
+    shutdown: CancellationToken,
+    runtime: Handle,
+    reloader: Arc<Reloader>,
+}
+
+impl ServerHandle {
+    pub(crate) fn new(
+        local_addr: SocketAddr,
+        secure: bool,
+        thread: JoinHandle<Result<(), ServerError>>,
+        shutdown: CancellationToken,
+        runtime: Handle,
+        reloader: Arc<Reloader>,
+    ) -> Self {
+        Self {
+            local_addr,
+            secure,
+            thread,
+            shutdown,
+            runtime,
+            reloader,
+        }
+    }
+
+    /// The address the server is listening on.
+    ///
+    /// When the configured port is `0`, this is where the port assigned by the OS can be found.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// The WebSocket URL clients can connect to, e.g. `ws://127.0.0.1:3000`, or
+    /// `wss://127.0.0.1:3000` when serving TLS.
+    ///
+    /// Unspecified bind addresses (`0.0.0.0`, `::`) are replaced by the matching loopback address.
+    pub fn url(&self) -> String {
+        let mut address = self.local_addr;
+        if address.ip().is_unspecified() {
+            address.set_ip(match address {
+                SocketAddr::V4(_) => std::net::Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => std::net::Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+
+        let scheme = if self.secure { "wss" } else { "ws" };
+        format!("{scheme}://{address}")
+    }
+
+    /// Ask the server to stop, without waiting for it.
+    ///
+    /// The server stops accepting connections, gives open connections up to the configured
+    /// shutdown timeout to finish, closes the remaining ones and releases the device memory.
+    /// Call [`join`](Self::join) to wait until it is done.
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Stop the server when the process receives Ctrl+C or SIGTERM.
+    pub fn shutdown_on_signal(&self) {
+        let shutdown = self.shutdown.clone();
+        self.runtime.spawn(async move {
+            tokio::select! {
+                _ = os_shutdown_signal() => {
+                    tracing::info!("Shutdown signal received, draining connections");
+                    shutdown.cancel();
+                }
+                _ = shutdown.cancelled() => {}
+            }
+        });
+    }
+
+    /// Read the configuration again and apply the settings that can change without a restart:
+    /// the tokens, the limits of the sessions and connections, and the log filter.
+    ///
+    /// Open sessions are kept. The report lists the changed settings that were applied and the
+    /// ones that only apply once the server is restarted, like the port or the devices. Nothing is
+    /// applied when the configuration is invalid.
+    ///
+    /// The configuration is read from the configuration file the server was started with and the
+    /// environment, unless another source was given with [`reload_from`](Self::reload_from).
+    /// Without either, the server keeps the configuration it was spawned with.
+    pub fn reload(&self) -> Result<ReloadReport, ServerError> {
+        self.reloader.reload()
+    }
+
+    /// Read the configuration from `source` on the next reloads, e.g. to apply command-line flags
+    /// on top of the configuration file.
+    pub fn reload_from(
+        &self,
+        source: impl Fn() -> Result<ServerConfig, ServerError> + Send + Sync + 'static,
+    ) {
+        self.reloader.set_source(Box::new(source));
+    }
+
+    /// [Reload](Self::reload) the configuration when the process receives SIGHUP.
+    ///
+    /// Does nothing on platforms without SIGHUP.
+    pub fn reload_on_signal(&self) {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let reloader = self.reloader.clone();
+            let shutdown = self.shutdown.clone();
+            self.runtime.spawn(async move {
+                let mut hangup = match signal(SignalKind::hangup()) {
+                    Ok(hangup) => hangup,
+                    Err(err) => {
+                        tracing::warn!(
+                            "Can't listen for SIGHUP to reload the configuration: {err}"
+                        );
+                        return;
+                    }
+                };
+                loop {
+                    tokio::select! {
+                        Some(()) = hangup.recv() => {}
+                        _ = shutdown.cancelled() => return,
+                    }
+
+                    tracing::info!("SIGHUP received, reloading the configuration");
+                    let reloader = reloader.clone();
+                    match tokio::task::spawn_blocking(move || reloader.reload()).await {
+                        Ok(Ok(_)) => {}
+                        Ok(Err(err)) => {
+                            tracing::error!("Can't reload the configuration: {err}")
+                        }
+                        Err(err) => tracing::error!("Can't reload the configuration: {err}"),
+                    }
+                }
+            });
+        }
+    }
+
+    /// Whether the server has stopped.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Wait for the server to stop.
+    pub fn join(self) -> Result<(), ServerError> {
+        self.thread
+            .join()
+            .unwrap_or_else(|payload| std::panic::resume_unwind(payload))
+    }
+}
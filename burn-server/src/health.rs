@@ -0,0 +1,136 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConnection, StreamOwned};
+
+use crate::{ServerConfig, ServerError};
+
+/// The HTTP endpoints reporting the health of a running server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthEndpoint {
+    /// `/healthz`: the process is alive and serving requests.
+    Alive,
+    /// `/readyz`: the device is able to run a tiny operation.
+    Ready,
+}
+
+impl HealthEndpoint {
+    /// The path of the endpoint.
+    pub fn path(self) -> &'static str {
+        match self {
+            HealthEndpoint::Alive => "/healthz",
+            HealthEndpoint::Ready => "/readyz",
+        }
+    }
+}
+
+impl fmt::Display for HealthEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.path())
+    }
+}
+
+/// Probe a health endpoint of the server running with the given configuration.
+///
+/// The server is reached on the loopback address when it listens on all interfaces, on the port
+/// read from the port file when the configured port is `0`, and over TLS, trusting only the
+/// configured certificate, when TLS is configured.
+///
+/// Returns the body of the response when the endpoint reports success.
+pub fn check_health(
+    config: &ServerConfig,
+    endpoint: HealthEndpoint,
+    timeout: Duration,
+) -> Result<String, ServerError> {
+    let address = server_address(config)?;
+    let scheme = if config.tls.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    let url = format!("{scheme}://{address}{endpoint}");
+    let unhealthy = |reason: String| ServerError::Unhealthy {
+        url: url.clone(),
+        reason,
+    };
+
+    let stream = TcpStream::connect_timeout(&address, timeout)
+        .and_then(|stream| {
+            stream.set_read_timeout(Some(timeout))?;
+            stream.set_write_timeout(Some(timeout))?;
+            Ok(stream)
+        })
+        .map_err(|err| unhealthy(err.to_string()))?;
+
+    let response = match &config.tls {
+        Some(tls) => {
+            let client_config = tls.pinned_client_config()?;
+            let connection = ClientConnection::new(
+                Arc::new(client_config),
+                ServerName::IpAddress(address.ip().into()),
+            )
+            .map_err(|err| unhealthy(err.to_string()))?;
+            get(StreamOwned::new(connection, stream), address, endpoint)
+        }
+        None => get(stream, address, endpoint),
+    }
+    .map_err(|err| unhealthy(err.to_string()))?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| unhealthy("malformed HTTP response".to_string()))?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| unhealthy("malformed HTTP response".to_string()))?;
+    let body = body.trim().to_string();
+
+    match status {
+        200 => Ok(body),
+        _ => Err(unhealthy(format!("HTTP {status}: {body}"))),
+    }
+}
+
+/// The address to reach the server on.
+fn server_address(config: &ServerConfig) -> Result<SocketAddr, ServerError> {
+    let ip = match config.bind_address {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    };
+
+    let port = match (config.port, &config.port_file) {
+        (0, Some(path)) => std::fs::read_to_string(path)
+            .map_err(|source| ServerError::PortFile {
+                path: path.clone(),
+                source,
+            })
+            .and_then(|port| crate::config::parse_port(&port))?,
+        (port, _) => port,
+    };
+
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Send a GET request and read the whole response, the server closing the connection.
+fn get<S: Read + Write>(
+    mut stream: S,
+    address: SocketAddr,
+    endpoint: HealthEndpoint,
+) -> std::io::Result<String> {
+    write!(
+        stream,
+        "GET {endpoint} HTTP/1.1\r\nHost: {address}\r\nConnection: close\r\n\r\n"
+    )?;
+    stream.flush()?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    Ok(response)
+}
@@ -0,0 +1,334 @@
+#![recursion_limit = "141"]
+// Without any backend feature the server can only report that no backend was selected.
+#![cfg_attr(
+    not(any(
+        feature = "cuda",
+        feature = "wgpu",
+        feature = "ndarray",
+        feature = "flex"
+    )),
+    allow(unused)
+)]
+
+mod auth;
+mod backend;
+mod config;
+mod config_file;
+mod device;
+mod error;
+mod handle;
+mod health;
+mod logging;
+mod placement;
+mod reload;
+mod server;
+mod tls;
+mod version;
+
+pub use auth::{read_token_file, Token, TOKEN_QUERY_PARAMETER};
+pub use backend::{Backend, DeviceSelector, Devices, WgpuAdapter};
+pub use config::{
+    parse_bind_address, parse_device, parse_devices, parse_heartbeat_interval,
+    parse_heartbeat_timeout, parse_log_format, parse_max_sessions, parse_placement,
+    parse_session_idle_timeout, parse_session_memory_quota, parse_session_queue_timeout,
+    parse_shutdown_timeout, tls_config, Limits, ServerConfig, DEFAULT_HEARTBEAT_INTERVAL,
+    DEFAULT_HEARTBEAT_TIMEOUT, DEFAULT_PORT, DEFAULT_SHUTDOWN_TIMEOUT,
+};
+pub use error::ServerError;
+pub use handle::ServerHandle;
+pub use health::{check_health, HealthEndpoint};
+pub use logging::{parse_log_filter, LogFormat, DEFAULT_LOG_FILTER};
+pub use placement::{Placement, DEVICE_HEADER};
+pub use reload::ReloadReport;
+pub use tls::TlsConfig;
+pub use version::{
+    check_client, server_description, Incompatible, BURN_VERSION, BURN_VERSION_HEADER,
+    COMPATIBLE_BURN_VERSIONS, PROTOCOL_REVISION, PROTOCOL_REVISION_HEADER, SERVER_VERSION_HEADER,
+};
+
+use burn::backend::ir::BackendIr;
+use device::DeviceTask;
+use reload::Reloader;
+use std::path::Path;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+/// Start the Burn remote backend server.
+///
+/// The server listens on the port specified by the REMOTE_BACKEND_PORT environment variable,
+/// defaulting to port 3000 if not set, on the interface specified by BURN_SERVER_BIND, defaulting
+/// to all IPv4 interfaces.
+///
+/// # Backends
+///
+/// Backends are compiled in via features:
+/// - `cuda` (default): NVIDIA CUDA backend for GPU acceleration
+/// - `wgpu`: WebGPU backend for cross-platform GPU acceleration
+/// - `ndarray`: NdArray CPU backend, no GPU required
+/// - `flex`: Pure-Rust CPU backend, no GPU or native libraries required
+///
+/// When several are compiled in, the BURN_SERVER_BACKEND environment variable selects one at
+/// runtime, otherwise the first available in the order above is used.
+pub fn start() -> Result<(), ServerError> {
+    start_with_config(ServerConfig::from_env()?)
+}
+
+/// Resolve the backend to run on.
+///
+/// Fails if the requested backend was not built into this binary, or if no backend was built at
+/// all.
+pub fn select_backend(requested: Option<Backend>) -> Result<Backend, ServerError> {
+    match requested {
+        Some(backend) if backend.is_available() => Ok(backend),
+        Some(_) => Err(ServerError::NoBackend { requested }),
+        None => Backend::preferred().ok_or(ServerError::NoBackend { requested: None }),
+    }
+}
+
+/// Check the configuration without starting the server.
+///
+/// Returns the backend the server would run on.
+pub fn check_config(config: &ServerConfig) -> Result<Backend, ServerError> {
+    check(config).map(|(backend, _)| backend)
+}
+
+/// Check the configuration, returning the backend and the devices to run on.
+fn check(config: &ServerConfig) -> Result<(Backend, Vec<DeviceSelector>), ServerError> {
+    let selected = select_devices(config)?;
+    config.tokens()?;
+    if let Some(tls) = &config.tls {
+        tls.acceptor()?;
+    }
+
+    Ok(selected)
+}
+
+/// Resolve the backend and the devices to run on, none for the default device of the backend.
+///
+/// The backend of a device selector like `cuda:2` is used when no backend is configured, and
+/// the devices must exist on this host.
+fn select_devices(config: &ServerConfig) -> Result<(Backend, Vec<DeviceSelector>), ServerError> {
+    let listed = match &config.devices {
+        Some(Devices::Selected(devices)) => devices.first().copied(),
+        _ => None,
+    };
+    let requested = config
+        .backend
+        .or(config.device.or(listed).and_then(|device| device.backend));
+    let backend = select_backend(requested)?;
+
+    let devices = match (&config.devices, config.device) {
+        (Some(devices), None) => device::resolve_devices(backend, devices)?,
+        (Some(devices), Some(device)) => {
+            return Err(ServerError::InvalidValue {
+                name: "devices",
+                value: devices.to_string(),
+                reason: format!(
+                    "a single device, {device}, is selected too, select either one device or the devices to serve"
+                ),
+            })
+        }
+        (None, device) => device
+            .map(|device| device::check_selector(backend, device))
+            .transpose()?
+            .into_iter()
+            .collect(),
+    };
+
+    Ok((backend, devices))
+}
+
+/// Initialize the configured devices without starting the server.
+///
+/// Returns the backend of the devices.
+pub fn probe_device(config: &ServerConfig) -> Result<Backend, ServerError> {
+    let (backend, devices) = check(config)?;
+    device::dispatch(backend, &devices, Probe)??;
+
+    Ok(backend)
+}
+
+/// Start the Burn remote backend server with the given configuration.
+///
+/// Blocks until the process receives a shutdown signal (Ctrl+C or SIGTERM) and the server has
+/// drained its connections. The configuration is reloaded on SIGHUP.
+pub fn start_with_config(config: ServerConfig) -> Result<(), ServerError> {
+    let handle = spawn(config)?;
+    handle.shutdown_on_signal();
+    handle.reload_on_signal();
+    handle.join()
+}
+
+/// Start the Burn remote backend server on a background thread.
+///
+/// Returns once the server is listening, with a [handle](ServerHandle) exposing the bound
+/// address, which is how the port assigned by the OS is found when the configured port is `0`.
+///
+/// The server runs until [`ServerHandle::shutdown`] is called, no signal handler is installed
+/// unless [`ServerHandle::shutdown_on_signal`] or [`ServerHandle::reload_on_signal`] is called.
+pub fn spawn(config: ServerConfig) -> Result<ServerHandle, ServerError> {
+    let (backend, devices) = check(&config)?;
+    let serve = Serve {
+        config,
+        devices: devices.clone(),
+    };
+
+    device::dispatch(backend, &devices, serve)?
+}
+
+struct Probe;
+
+impl DeviceTask for Probe {
+    type Output = Result<(), ServerError>;
+
+    fn run<B: BackendIr>(self, backend: Backend, devices: Vec<B::Device>) -> Self::Output {
+        let several = devices.len() > 1;
+        for device in devices {
+            device::init::<B>(&device).map_err(|reason| ServerError::DeviceInit {
+                backend,
+                reason: if several {
+                    format!("{device:?}: {reason}")
+                } else {
+                    reason
+                },
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+struct Serve {
+    config: ServerConfig,
+    /// The selected devices, none for the default device of the backend.
+    devices: Vec<DeviceSelector>,
+}
+
+impl DeviceTask for Serve {
+    type Output = Result<ServerHandle, ServerError>;
+
+    /// Initialize the device, listen on the configured address and serve clients on a background
+    /// thread until shutdown.
+    fn run<B: BackendIr>(self, backend: Backend, devices: Vec<B::Device>) -> Self::Output {
+        let config = self.config;
+        let address = config.socket_addr();
+
+        logging::init(&config.log_filter, config.log_format);
+
+        tracing::info!("Starting Burn Remote Backend Server on {address}");
+        tracing::info!("Backend: {}", backend.description());
+        for device in &devices {
+            tracing::info!("Device: {device:?}");
+        }
+
+        Probe.run::<B>(backend, devices.clone())?;
+
+        let nested = config.devices.is_some();
+        let devices = devices
+            .into_iter()
+            .enumerate()
+            .map(|(position, device)| server::ServedDevice {
+                name: match self.devices.get(position) {
+                    Some(selector) => selector.to_string(),
+                    None => backend.name().to_string(),
+                },
+                device,
+            })
+            .collect::<Vec<_>>();
+
+        let tokens = config.tokens()?;
+        let tls = config.tls.as_ref().map(TlsConfig::acceptor).transpose()?;
+        let secure = tls.is_some();
+        if tokens.is_empty() {
+            if !config.bind_address.is_loopback() {
+                tracing::warn!(
+                    "Authentication is disabled and the server listens on {}, anyone who can reach it can run work on the device. Set BURN_SERVER_TOKEN or BURN_SERVER_TOKEN_FILE to require a token.",
+                    config.bind_address
+                );
+            }
+        } else {
+            tracing::info!("Authentication: {} token(s) accepted", tokens.len());
+        }
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+
+        let settings = Arc::new(server::Settings::new(tokens, config.limits.clone()));
+        let server = runtime
+            .block_on(server::WsServer::bind(
+                address,
+                &config,
+                settings.clone(),
+                tls,
+            ))
+            .map_err(|source| match source.kind() {
+                std::io::ErrorKind::AddrInUse => ServerError::PortInUse { address },
+                _ => ServerError::Bind { address, source },
+            })?;
+        let local_addr = server.local_addr()?;
+        let scheme = if secure { "wss" } else { "ws" };
+        tracing::info!("Listening on {scheme}://{local_addr}");
+        if nested {
+            for (position, device) in devices.iter().enumerate() {
+                tracing::info!(
+                    "Serving {} on {scheme}://{local_addr}/device/{position}",
+                    device.name
+                );
+            }
+            tracing::info!(
+                "Placing the sessions connecting to the root on the device with the {}",
+                match config.placement {
+                    Placement::Sessions => "fewest sessions",
+                    Placement::MemoryInUse => "least memory in use",
+                }
+            );
+        }
+
+        if let Some(path) = &config.port_file {
+            write_port_file(path, local_addr.port())?;
+        }
+
+        let placement = config.placement;
+        let reloader = Arc::new(Reloader::new(config, settings.clone()));
+        let server = server.merge(server::admin_router(reloader.clone(), settings));
+
+        let shutdown = CancellationToken::new();
+        let runtime_handle = runtime.handle().clone();
+        let thread = std::thread::Builder::new()
+            .name("burn-server".to_string())
+            .spawn({
+                let shutdown = shutdown.clone();
+                move || {
+                    runtime.block_on(server::serve::<B>(
+                        devices, nested, placement, server, shutdown,
+                    ))?;
+                    tracing::info!("Server stopped");
+                    Ok(())
+                }
+            })?;
+
+        Ok(ServerHandle::new(
+            local_addr,
+            secure,
+            thread,
+            shutdown,
+            runtime_handle,
+            reloader,
+        ))
+    }
+}
+
+/// Write the bound port to a file, replacing it atomically so that readers never see a partial
+/// write.
+fn write_port_file(path: &Path, port: u16) -> Result<(), ServerError> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    std::fs::write(&tmp, format!("{port}\n"))
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|source| ServerError::PortFile {
+            path: path.to_path_buf(),
+            source,
+        })
+}
@@ -0,0 +1,222 @@
+use std::fmt;
+use std::io::{IsTerminal, Write};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::{FormatTime, SystemTime};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+use crate::ServerError;
+
+/// Default filter of the log records, the wgpu crates being too verbose at the `info` level.
+pub const DEFAULT_LOG_FILTER: &str = "info,wgpu=warn";
+
+/// Replaces the filter of the logger installed by [`init`].
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Format of the log records written to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Text,
+    /// One JSON object per line, for log shippers.
+    Json,
+}
+
+impl LogFormat {
+    /// Every format, in the order they are listed to users.
+    pub const ALL: [LogFormat; 2] = [LogFormat::Text, LogFormat::Json];
+
+    /// The name used to select the format.
+    pub fn name(self) -> &'static str {
+        match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown log format {s:?}, expected one of: {}",
+                    Self::ALL.map(|format| format.name()).join(", ")
+                )
+            })
+    }
+}
+
+/// Parse a log filter in the syntax of `RUST_LOG`, e.g. `info,burn_server=debug`.
+pub fn parse_log_filter(value: &str) -> Result<String, ServerError> {
+    let filter = value.trim();
+    EnvFilter::builder()
+        .parse(filter)
+        .map(|_| filter.to_string())
+        .map_err(|err| ServerError::InvalidValue {
+            name: "RUST_LOG",
+            value: value.to_string(),
+            reason: err.to_string(),
+        })
+}
+
+/// Install the logger writing the records matching the filter to stdout.
+///
+/// Records of the `log` crate are forwarded to it. Only the first call has an effect, so that
+/// several servers can run in the same process.
+pub(crate) fn init(filter: &str, format: LogFormat) {
+    let (filter, handle) = reload::Layer::new(EnvFilter::builder().parse_lossy(filter));
+    let layer = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            // No escape codes in log files.
+            .with_ansi(std::io::stdout().is_terminal())
+            .boxed(),
+        LogFormat::Json => JsonLayer.boxed(),
+    };
+
+    let installed = tracing_subscriber::registry()
+        .with(layer.with_filter(filter))
+        .try_init();
+    if installed.is_ok() {
+        let _ = FILTER.set(handle);
+    }
+}
+
+/// Replace the filter of the log records of the logger installed by [`init`], if any.
+pub(crate) fn set_filter(filter: &str) {
+    if let Some(handle) = FILTER.get() {
+        if let Err(err) = handle.reload(EnvFilter::builder().parse_lossy(filter)) {
+            tracing::warn!("Can't change the log filter: {err}");
+        }
+    }
+}
+
+/// Writes each event as a JSON object on its own line, in the layout of the JSON format of
+/// `tracing-subscriber`: `timestamp`, `level`, `target`, the event `fields`, the innermost `span`
+/// and all `spans` from the root, each with its `name` and fields.
+struct JsonLayer;
+
+/// Fields recorded on a span, stored in its extensions.
+struct SpanFields(Map<String, Value>);
+
+impl<S> Layer<S> for JsonLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Map::new();
+        fields.insert("name".to_string(), span.name().into());
+        attrs.record(&mut JsonVisitor(&mut fields));
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut JsonVisitor(fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        // Records of the `log` crate carry their metadata in fields.
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+
+        let mut timestamp = String::new();
+        let _ = SystemTime.format_time(&mut Writer::new(&mut timestamp));
+
+        let mut fields = Map::new();
+        event.record(&mut JsonVisitor(&mut fields));
+
+        let spans: Vec<Value> = ctx
+            .event_scope(event)
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+            .filter_map(|span| {
+                let extensions = span.extensions();
+                let SpanFields(fields) = extensions.get::<SpanFields>()?;
+                Some(Value::Object(fields.clone()))
+            })
+            .collect();
+
+        let mut record = Map::new();
+        record.insert("timestamp".to_string(), timestamp.into());
+        record.insert("level".to_string(), metadata.level().to_string().into());
+        record.insert("target".to_string(), metadata.target().into());
+        record.insert("fields".to_string(), Value::Object(fields));
+        if let Some(span) = spans.last() {
+            record.insert("span".to_string(), span.clone());
+            record.insert("spans".to_string(), Value::Array(spans));
+        }
+
+        let mut line = Value::Object(record).to_string();
+        line.push('\n');
+        // Logging must not take the server down when stdout is closed.
+        let _ = std::io::stdout().lock().write_all(line.as_bytes());
+    }
+}
+
+/// Records fields into a JSON object, skipping the metadata fields of `log` records.
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl JsonVisitor<'_> {
+    fn insert(&mut self, field: &Field, value: Value) {
+        if !field.name().starts_with("log.") {
+            self.0.insert(field.name().to_string(), value);
+        }
+    }
+}
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, format!("{value:?}").into());
+    }
+}
@@ -0,0 +1,483 @@
+//! Burn Remote Backend Server
+//!
+//! This server provides GPU-accelerated tensor operations via WebSocket.
+//! Connect your remote Burn client to this server to leverage CUDA GPU acceleration.
+//!
+//! `burn-server --help` describes the subcommands, the options and the exit codes.
+
+use burn_server::{
+    Backend, DeviceSelector, Devices, HealthEndpoint, LogFormat, Placement, ServerConfig,
+    ServerError,
+};
+use clap::{Args, Parser, Subcommand};
+use std::ffi::OsString;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The usage notes of `--help`, next to the options.
+const LONG_ABOUT: &str = "\
+Burn Remote Backend Server
+
+Serves the remote backend of burn over WebSocket, on ws://<bind>:<port>, or wss:// with --tls-cert \
+and --tls-key.
+
+Flags override the environment variables named in their description, which override the TOML \
+configuration file given with --config, which overrides the defaults. `burn-server check-config` \
+prints the merged configuration.
+
+With --devices, the device at position n is served under /device/<n>, and the sessions connecting \
+to the root are placed on a device according to --placement for their lifetime.
+
+On SIGHUP, or a POST /admin/reload request presenting a token, the configuration is read again and \
+the tokens, the limits and the log filter are applied without closing any session.
+
+On Ctrl+C or SIGTERM the server stops accepting connections and gives open sessions up to \
+--shutdown-timeout seconds to finish.
+
+/healthz, /readyz, /metrics and /version are served over HTTP(S) on the server port, without \
+authentication. POST /admin/reload is served on the same port and requires a token, or a request \
+from the loopback interface when authentication is disabled.";
+
+/// The exit codes, from `sysexits.h`, listed at the end of `--help`.
+const EXIT_CODES: &str = "\
+Exit codes:
+  1   healthcheck found the server unhealthy
+  2   invalid command-line arguments
+  69  the requested backend or device isn't available on this host
+  70  the device failed to initialize
+  71  the server can't listen on the address
+  73  the port file can't be written
+  74  I/O error while serving clients
+  75  the port is already in use
+  78  invalid configuration, e.g. an invalid setting, an unreadable token file or TLS key";
+
+#[derive(Parser)]
+#[command(
+    name = "burn-server",
+    version,
+    about = "Burn Remote Backend Server",
+    long_about = LONG_ABOUT,
+    after_long_help = EXIT_CODES
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    options: Options,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Start the server (default).
+    Serve,
+    /// Print the version, the compiled-in backends and the status of the configured device.
+    Info,
+    /// Validate and print the effective configuration without starting the server.
+    CheckConfig,
+    /// Probe the health endpoints of the running server, exit with 0 when healthy and 1
+    /// otherwise.
+    Healthcheck {
+        /// Only check that the server is alive (/healthz) instead of ready (/readyz).
+        #[arg(long)]
+        live: bool,
+
+        /// Seconds to wait for the server to respond.
+        #[arg(long, default_value = "5", value_parser = parse_timeout)]
+        timeout: Duration,
+    },
+    /// Print the version.
+    Version,
+}
+
+#[derive(Args, Clone)]
+struct Options {
+    /// TOML configuration file, overridden by the environment and the flags
+    /// [env: BURN_SERVER_CONFIG].
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Port to listen on, 0 for a port assigned by the OS [env: REMOTE_BACKEND_PORT]
+    /// [default: 3000].
+    #[arg(long, short, global = true)]
+    port: Option<u16>,
+
+    /// Address of the interface to listen on, e.g. 127.0.0.1 or :: [env: BURN_SERVER_BIND]
+    /// [default: 0.0.0.0].
+    #[arg(long, global = true, value_parser = parse_bind_address)]
+    bind: Option<IpAddr>,
+
+    /// Backend to run on: cuda, wgpu, ndarray or flex [env: BURN_SERVER_BACKEND].
+    #[arg(long, global = true)]
+    backend: Option<Backend>,
+
+    /// Device to run on, e.g. 2, cuda:2, wgpu:1 or wgpu:integrated:0 [env: BURN_SERVER_DEVICE]
+    /// [default: the default device of the backend].
+    #[arg(long, global = true, value_parser = parse_device)]
+    device: Option<DeviceSelector>,
+
+    /// Devices to serve under /device/<n>: all, or a list like 0,1 or cuda:0,cuda:2
+    /// [env: BURN_SERVER_DEVICES] [default: only --device].
+    #[arg(long, global = true, conflicts_with = "device", value_parser = parse_devices)]
+    devices: Option<Devices>,
+
+    /// How the sessions connecting to the root are placed on the --devices: sessions, the device
+    /// with the fewest sessions, or memory-in-use, the one with the least memory used by tensors
+    /// [env: BURN_SERVER_PLACEMENT] [default: sessions].
+    #[arg(long, global = true)]
+    placement: Option<Placement>,
+
+    /// Write the bound port to this file once listening [env: BURN_SERVER_PORT_FILE].
+    #[arg(long, global = true)]
+    port_file: Option<PathBuf>,
+
+    /// Seconds given to open connections to finish on shutdown before they are closed
+    /// [env: BURN_SERVER_SHUTDOWN_TIMEOUT] [default: 30].
+    #[arg(long, global = true, value_parser = parse_shutdown_timeout)]
+    shutdown_timeout: Option<Duration>,
+
+    /// Maximum number of simultaneous client sessions [env: BURN_SERVER_MAX_SESSIONS]
+    /// [default: unlimited].
+    #[arg(long, global = true, value_parser = parse_max_sessions)]
+    max_sessions: Option<usize>,
+
+    /// Seconds a new session waits for a free slot when --max-sessions are open before it is
+    /// rejected, 0 to reject it right away [env: BURN_SERVER_SESSION_QUEUE_TIMEOUT] [default: 0].
+    #[arg(long, global = true, value_parser = parse_session_queue_timeout)]
+    session_queue_timeout: Option<Duration>,
+
+    /// Bytes of tensors a session may hold on the device, with an optional unit, e.g. 4GiB
+    /// [env: BURN_SERVER_SESSION_MEMORY_QUOTA] [default: unlimited].
+    #[arg(long, global = true, value_parser = parse_session_memory_quota)]
+    session_memory_quota: Option<u64>,
+
+    /// Seconds a session may go without requests before it is closed and its tensors freed, 0
+    /// for never [env: BURN_SERVER_SESSION_IDLE_TIMEOUT] [default: 0].
+    #[arg(long, global = true, value_parser = parse_session_idle_timeout)]
+    session_idle_timeout: Option<Duration>,
+
+    /// Seconds between TCP keepalive probes sent to idle clients to detect peers that went away,
+    /// 0 to disable them [env: BURN_SERVER_HEARTBEAT_INTERVAL] [default: 15].
+    #[arg(long, global = true, value_parser = parse_heartbeat_interval)]
+    heartbeat_interval: Option<Duration>,
+
+    /// Seconds a client may go without answering before its session is closed and its tensors
+    /// freed [env: BURN_SERVER_HEARTBEAT_TIMEOUT] [default: 60].
+    #[arg(long, global = true, value_parser = parse_heartbeat_timeout)]
+    heartbeat_timeout: Option<Duration>,
+
+    /// File with the tokens clients must present to connect, one per line
+    /// [env: BURN_SERVER_TOKEN_FILE].
+    #[arg(long, global = true)]
+    token_file: Option<PathBuf>,
+
+    /// Accept clients that don't report their version of burn in the x-burn-version header, like
+    /// burn's own remote client, rejected by default
+    /// [env: BURN_SERVER_ALLOW_UNVERSIONED_CLIENTS].
+    #[arg(long, global = true)]
+    allow_unversioned_clients: bool,
+
+    /// PEM file with the TLS certificate chain, to serve wss:// [env: BURN_SERVER_TLS_CERT].
+    #[arg(long, global = true, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM file with the private key of the TLS certificate [env: BURN_SERVER_TLS_KEY].
+    #[arg(long, global = true, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Filter of the log records, e.g. info,burn_server=debug [env: RUST_LOG]
+    /// [default: info,wgpu=warn].
+    #[arg(long, global = true, value_parser = parse_log_filter)]
+    log_filter: Option<String>,
+
+    /// Format of the log records: text or json [env: BURN_SERVER_LOG_FORMAT] [default: text].
+    #[arg(long, global = true)]
+    log_format: Option<LogFormat>,
+}
+
+impl Options {
+    /// The configuration from the configuration file and the environment, overridden by the
+    /// flags.
+    fn config(self) -> Result<ServerConfig, ServerError> {
+        self.config_with_env(|name| std::env::var_os(name))
+    }
+
+    /// Like [`config`](Self::config), with the environment variables looked up by `env`.
+    fn config_with_env(
+        self,
+        env: impl Fn(&str) -> Option<OsString>,
+    ) -> Result<ServerConfig, ServerError> {
+        let mut config = ServerConfig::load_with_env(self.config, env)?;
+
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if let Some(bind) = self.bind {
+            config.bind_address = bind;
+        }
+        if let Some(backend) = self.backend {
+            config.backend = Some(backend);
+        }
+        // A device given as a flag replaces the devices given in the environment or the file, and
+        // the other way around.
+        if let Some(device) = self.device {
+            config.device = Some(device);
+            config.devices = None;
+        }
+        if let Some(devices) = self.devices {
+            config.device = None;
+            config.devices = Some(devices);
+        }
+        if let Some(placement) = self.placement {
+            config.placement = placement;
+        }
+        if let Some(path) = self.port_file {
+            config.port_file = Some(path);
+        }
+        if let Some(timeout) = self.shutdown_timeout {
+            config.shutdown_timeout = timeout;
+        }
+        if let Some(count) = self.max_sessions {
+            config.limits.max_sessions = Some(count);
+        }
+        if let Some(timeout) = self.session_queue_timeout {
+            config.limits.session_queue_timeout = timeout;
+        }
+        if let Some(bytes) = self.session_memory_quota {
+            config.limits.session_memory_quota = Some(bytes);
+        }
+        if let Some(timeout) = self.session_idle_timeout {
+            config.limits.session_idle_timeout = Some(timeout).filter(|timeout| !timeout.is_zero());
+        }
+        if let Some(interval) = self.heartbeat_interval {
+            config.limits.heartbeat_interval = interval;
+        }
+        if let Some(timeout) = self.heartbeat_timeout {
+            config.limits.heartbeat_timeout = timeout;
+        }
+        if let Some(path) = self.token_file {
+            config.token_file = Some(path);
+        }
+        if self.allow_unversioned_clients {
+            config.allow_unversioned_clients = true;
+        }
+        if self.tls_cert.is_some() || self.tls_key.is_some() {
+            let (cert, key) = config.tls.take().map(|tls| (tls.cert, tls.key)).unzip();
+            config.tls = burn_server::tls_config(self.tls_cert.or(cert), self.tls_key.or(key))?;
+        }
+        if let Some(filter) = self.log_filter {
+            config.log_filter = filter;
+        }
+        if let Some(format) = self.log_format {
+            config.log_format = format;
+        }
+
+        Ok(config)
+    }
+}
+
+fn parse_bind_address(value: &str) -> Result<IpAddr, String> {
+    burn_server::parse_bind_address(value).map_err(flag_error)
+}
+
+fn parse_device(value: &str) -> Result<DeviceSelector, String> {
+    burn_server::parse_device(value).map_err(flag_error)
+}
+
+fn parse_devices(value: &str) -> Result<Devices, String> {
+    burn_server::parse_devices(value).map_err(flag_error)
+}
+
+fn parse_shutdown_timeout(value: &str) -> Result<Duration, String> {
+    burn_server::parse_shutdown_timeout(value).map_err(flag_error)
+}
+
+fn parse_max_sessions(value: &str) -> Result<usize, String> {
+    burn_server::parse_max_sessions(value).map_err(flag_error)
+}
+
+fn parse_session_queue_timeout(value: &str) -> Result<Duration, String> {
+    burn_server::parse_session_queue_timeout(value).map_err(flag_error)
+}
+
+fn parse_session_memory_quota(value: &str) -> Result<u64, String> {
+    burn_server::parse_session_memory_quota(value).map_err(flag_error)
+}
+
+/// Zero is kept to override a timeout set in the environment.
+fn parse_session_idle_timeout(value: &str) -> Result<Duration, String> {
+    burn_server::parse_session_idle_timeout(value)
+        .map(Option::unwrap_or_default)
+        .map_err(flag_error)
+}
+
+fn parse_heartbeat_interval(value: &str) -> Result<Duration, String> {
+    burn_server::parse_heartbeat_interval(value).map_err(flag_error)
+}
+
+fn parse_heartbeat_timeout(value: &str) -> Result<Duration, String> {
+    burn_server::parse_heartbeat_timeout(value).map_err(flag_error)
+}
+
+fn parse_log_filter(value: &str) -> Result<String, String> {
+    burn_server::parse_log_filter(value).map_err(flag_error)
+}
+
+/// The reason an invalid flag value is rejected, clap naming the flag rather than the environment
+/// variable the parsers of the library report.
+fn flag_error(err: ServerError) -> String {
+    match err {
+        ServerError::InvalidValue { reason, .. } => reason,
+        err => err.to_string(),
+    }
+}
+
+fn parse_timeout(value: &str) -> Result<Duration, String> {
+    value
+        .parse::<f64>()
+        .map_err(|err| err.to_string())
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).map_err(|err| err.to_string()))
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let result = match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(cli.options),
+        Command::Info => cli.options.config().map(info),
+        Command::CheckConfig => cli.options.config().and_then(check_config),
+        Command::Healthcheck { live, timeout } => cli
+            .options
+            .config()
+            .and_then(|config| healthcheck(config, live, timeout)),
+        Command::Version => {
+            version();
+            Ok(())
+        }
+    };
+
+    if let Err(err) = result {
+        eprintln!("burn-server: {err}");
+        std::process::exit(err.exit_code());
+    }
+}
+
+/// Serve until a shutdown signal, reloading the configuration with the same flags on SIGHUP.
+fn serve(options: Options) -> Result<(), ServerError> {
+    let handle = burn_server::spawn(options.clone().config()?)?;
+    handle.reload_from(move || options.clone().config());
+    handle.shutdown_on_signal();
+    handle.reload_on_signal();
+    handle.join()
+}
+
+fn version() {
+    println!("{}", burn_server::server_description());
+}
+
+fn info(config: ServerConfig) {
+    version();
+
+    let preferred = Backend::preferred();
+    println!("\nBackends:");
+    for backend in Backend::ALL {
+        let status = match (backend.is_available(), Some(backend) == preferred) {
+            (true, true) => "available (default)",
+            (true, false) => "available",
+            (false, _) => "not compiled in",
+        };
+        println!(
+            "  {:<8} {:<14} {status}",
+            backend.name(),
+            backend.description()
+        );
+    }
+
+    println!("\nConfiguration:");
+    for line in config.to_string().lines() {
+        println!("  {line}");
+    }
+
+    println!();
+    match burn_server::probe_device(&config) {
+        Ok(backend) => println!("Device: {} ready", backend.description()),
+        Err(err) => println!("Device: {err}"),
+    }
+}
+
+fn check_config(config: ServerConfig) -> Result<(), ServerError> {
+    println!("{config}");
+    let backend = burn_server::check_config(&config)?;
+    println!(
+        "\nConfiguration is valid, the server will run on {}",
+        backend.description()
+    );
+
+    Ok(())
+}
+
+fn healthcheck(config: ServerConfig, live: bool, timeout: Duration) -> Result<(), ServerError> {
+    let endpoint = if live {
+        HealthEndpoint::Alive
+    } else {
+        HealthEndpoint::Ready
+    };
+    let body = burn_server::check_health(&config, endpoint, timeout)?;
+    println!("{body}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn flags_override_the_environment_and_the_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(
+            b"[network]\nport = 4000\n\n[limits]\nmax_sessions = 2\nsession_queue_timeout = 2\n",
+        )
+        .unwrap();
+
+        let cli = Cli::parse_from([
+            "burn-server",
+            "--config",
+            file.path().to_str().unwrap(),
+            "--max-sessions",
+            "4",
+        ]);
+        let config = cli
+            .options
+            .config_with_env(|name| match name {
+                "BURN_SERVER_MAX_SESSIONS" => Some("3".into()),
+                "BURN_SERVER_SESSION_QUEUE_TIMEOUT" => Some("5".into()),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(config.port, 4000);
+        assert_eq!(config.limits.max_sessions, Some(4));
+        assert_eq!(config.limits.session_queue_timeout, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn invalid_flags_are_reported_by_name() {
+        for (flag, value) in [
+            ("--device", "cuda:x"),
+            ("--devices", "0,x"),
+            ("--max-sessions", "x"),
+            ("--session-memory-quota", "0.5B"),
+            ("--heartbeat-timeout", "soon"),
+        ] {
+            let Err(err) = Cli::try_parse_from(["burn-server", flag, value]) else {
+                panic!("{flag} {value} should be rejected");
+            };
+            let message = err.to_string();
+            assert!(message.contains(&format!("'{flag} <")), "{message}");
+            assert!(!message.contains("BURN_SERVER_"), "{message}");
+        }
+    }
+}
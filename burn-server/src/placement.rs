@@ -0,0 +1,62 @@
+//! Placement of new sessions on the devices of a server serving several of them.
+//!
+//! Clients connecting to the root of such a server, instead of `/device/<n>`, get their session
+//! placed on the least loaded device according to the [policy](Placement), for the lifetime of the
+//! session. The server answers the handshake of the request connection with the name of the chosen
+//! device in the [`DEVICE_HEADER`] header.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Header carrying the name of the device a session runs on, e.g. `cuda:1`, in the responses to
+/// the handshakes of servers serving several devices.
+pub const DEVICE_HEADER: &str = "x-burn-server-device";
+
+/// How a server serving several devices chooses the device of a new session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Placement {
+    /// The device with the fewest active sessions.
+    #[default]
+    Sessions,
+    /// The device with the least memory used by tensors, then the fewest active sessions. The
+    /// memory left free isn't known, so devices of different sizes are ranked by their use alone.
+    /// Backends that don't report their memory, like the CPU ones, fall back to the fewest active
+    /// sessions.
+    MemoryInUse,
+}
+
+impl Placement {
+    /// Every policy, in the order they are listed to users.
+    pub const ALL: [Placement; 2] = [Placement::Sessions, Placement::MemoryInUse];
+
+    /// The name used to select the policy.
+    pub fn name(self) -> &'static str {
+        match self {
+            Placement::Sessions => "sessions",
+            Placement::MemoryInUse => "memory-in-use",
+        }
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|placement| placement.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown placement policy {s:?}, expected one of: {}",
+                    Self::ALL.map(|placement| placement.name()).join(", ")
+                )
+            })
+    }
+}
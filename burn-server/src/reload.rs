@@ -0,0 +1,210 @@
+//! Reloading the configuration of a running server, on SIGHUP or `POST /admin/reload`.
+//!
+//! The configuration is read again like at startup, from the configuration file and the
+//! environment, and the settings that can change live are applied without closing any session:
+//!
+//! - `tokens`, from `token` and `token_file`, the token file being read again: new connections
+//!   must present one of the new tokens, open sessions are kept.
+//! - `max_sessions`, `session_queue_timeout` and `session_memory_quota`: applied to the sessions
+//!   opened next. Lowering `max_sessions` below the open sessions doesn't close any, new ones wait
+//!   until enough closed.
+//! - `session_idle_timeout`: applied to the open sessions too.
+//! - `max_message_size`, `max_frame_size`, `heartbeat_interval` and `heartbeat_timeout`: applied
+//!   to the connections opened next.
+//! - `log_filter`: applied right away.
+//!
+//! The other settings, like the port, the devices or TLS, are reported as requiring a restart and
+//! keep their running value.
+//!
+//! A server spawned with a configuration built in code, without a configuration file, has nothing
+//! to read again until a source is given with [`reload_from`](crate::ServerHandle::reload_from):
+//! reloading it keeps its configuration, settings set in code like its token included.
+
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::config::ServerConfig;
+use crate::logging;
+use crate::server::Settings;
+use crate::ServerError;
+
+/// Where the configuration is read from on reload.
+type Source = Box<dyn Fn() -> Result<ServerConfig, ServerError> + Send + Sync>;
+
+/// The outcome of a [reload](crate::ServerHandle::reload), by setting name as printed by
+/// `burn-server check-config`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReloadReport {
+    /// The changed settings that were applied.
+    pub applied: Vec<&'static str>,
+    /// The changed settings that only apply once the server is restarted.
+    pub restart_required: Vec<&'static str>,
+}
+
+impl fmt::Display for ReloadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |names: &[&str]| match names {
+            [] => "(none)".to_string(),
+            names => names.join(", "),
+        };
+        write!(
+            f,
+            "applied: {}; restart required: {}",
+            list(&self.applied),
+            list(&self.restart_required)
+        )
+    }
+}
+
+/// Reads the configuration again and applies the settings that can change live to a running
+/// server.
+pub(crate) struct Reloader {
+    /// The configuration the server runs with, its live settings updated on reload.
+    running: Mutex<ServerConfig>,
+    /// `None` when the server has no configuration file and no source was given.
+    source: RwLock<Option<Source>>,
+    settings: Arc<Settings>,
+}
+
+impl Reloader {
+    /// A reloader of the server started with the configuration, reading it again from the same
+    /// configuration file and the environment when it has one.
+    pub fn new(config: ServerConfig, settings: Arc<Settings>) -> Self {
+        let source = config
+            .config_file
+            .clone()
+            .map(|path| Box::new(move || ServerConfig::load(Some(path.clone()))) as Source);
+
+        Self {
+            running: Mutex::new(config),
+            source: RwLock::new(source),
+            settings,
+        }
+    }
+
+    /// Read the configuration from the source on the next reloads instead.
+    pub fn set_source(&self, source: Source) {
+        *self.source.write().unwrap() = Some(source);
+    }
+
+    /// Whether there is a configuration to read again, from a configuration file or a source.
+    pub fn is_configured(&self) -> bool {
+        self.source.read().unwrap().is_some()
+    }
+
+    /// Read the configuration again and apply the settings that can change live.
+    ///
+    /// Nothing is applied when the configuration is invalid, nothing changes when there is no
+    /// configuration to read again.
+    pub fn reload(&self) -> Result<ReloadReport, ServerError> {
+        let config = match &*self.source.read().unwrap() {
+            Some(source) => source()?,
+            None => {
+                tracing::info!("No configuration file to read again, the configuration is kept");
+                return Ok(ReloadReport::default());
+            }
+        };
+        let tokens = config.tokens()?;
+
+        let mut running = self.running.lock().unwrap();
+        let mut report = ReloadReport::default();
+        let mut changed = |name, differs: bool, live: bool| {
+            if differs && live {
+                report.applied.push(name);
+            } else if differs {
+                report.restart_required.push(name);
+            }
+        };
+
+        let (old, new) = (&running.limits, &config.limits);
+        changed("tokens", *self.settings.tokens() != *tokens, true);
+        changed(
+            "max_message_size",
+            old.max_message_size != new.max_message_size,
+            true,
+        );
+        changed(
+            "max_frame_size",
+            old.max_frame_size != new.max_frame_size,
+            true,
+        );
+        changed("max_sessions", old.max_sessions != new.max_sessions, true);
+        changed(
+            "session_queue_timeout",
+            old.session_queue_timeout != new.session_queue_timeout,
+            true,
+        );
+        changed(
+            "session_memory_quota",
+            old.session_memory_quota != new.session_memory_quota,
+            true,
+        );
+        changed(
+            "session_idle_timeout",
+            old.session_idle_timeout != new.session_idle_timeout,
+            true,
+        );
+        changed(
+            "heartbeat_interval",
+            old.heartbeat_interval != new.heartbeat_interval,
+            true,
+        );
+        changed(
+            "heartbeat_timeout",
+            old.heartbeat_timeout != new.heartbeat_timeout,
+            true,
+        );
+        changed("log_filter", running.log_filter != config.log_filter, true);
+
+        changed("port", running.port != config.port, false);
+        changed(
+            "bind_address",
+            running.bind_address != config.bind_address,
+            false,
+        );
+        changed("backend", running.backend != config.backend, false);
+        changed("device", running.device != config.device, false);
+        changed("devices", running.devices != config.devices, false);
+        changed("placement", running.placement != config.placement, false);
+        changed("port_file", running.port_file != config.port_file, false);
+        changed(
+            "shutdown_timeout",
+            running.shutdown_timeout != config.shutdown_timeout,
+            false,
+        );
+        changed(
+            "allow_unversioned_clients",
+            running.allow_unversioned_clients != config.allow_unversioned_clients,
+            false,
+        );
+        changed("tls", running.tls != config.tls, false);
+        changed("log_format", running.log_format != config.log_format, false);
+
+        self.settings.set_tokens(tokens);
+        self.settings.set_limits(config.limits.clone());
+        if running.log_filter != config.log_filter {
+            logging::set_filter(&config.log_filter);
+        }
+        running.token = config.token;
+        running.token_file = config.token_file;
+        running.limits = config.limits;
+        running.log_filter = config.log_filter;
+
+        if report.applied.is_empty() {
+            tracing::info!("Configuration reloaded, no setting changed that can change live");
+        } else {
+            tracing::info!(
+                "Configuration reloaded, applied: {}",
+                report.applied.join(", ")
+            );
+        }
+        if !report.restart_required.is_empty() {
+            tracing::warn!(
+                "Changed settings only applied after a restart: {}",
+                report.restart_required.join(", ")
+            );
+        }
+
+        Ok(report)
+    }
+}
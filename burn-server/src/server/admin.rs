@@ -0,0 +1,84 @@
+use axum::{
+    extract::{ConnectInfo, RawQuery, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::auth;
+use crate::reload::Reloader;
+
+use super::settings::Settings;
+use super::websocket::unauthorized;
+
+struct Admin {
+    reloader: Arc<Reloader>,
+    settings: Arc<Settings>,
+}
+
+/// HTTP routes administering the running server:
+///
+/// - `POST /admin/reload`: read the configuration again and apply the settings that can change
+///   live, answering with the applied settings and the ones requiring a restart as JSON.
+///
+/// Requests must present one of the accepted tokens like WebSocket clients, and only come from
+/// the loopback interface when authentication is disabled. The route is only found once there is
+/// a configuration to read again, from a configuration file or a
+/// [source](crate::ServerHandle::reload_from), which may be given after the server started.
+pub fn router(reloader: Arc<Reloader>, settings: Arc<Settings>) -> Router {
+    Router::new()
+        .route("/admin/reload", post(reload))
+        .with_state(Arc::new(Admin { reloader, settings }))
+}
+
+async fn reload(
+    State(admin): State<Arc<Admin>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    RawQuery(query): RawQuery,
+) -> Response {
+    if !admin.reloader.is_configured() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let tokens = admin.settings.tokens();
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    if !auth::is_authorized(&tokens, authorization, query.as_deref()) {
+        tracing::warn!("Rejected unauthenticated reload from {peer}");
+        return unauthorized();
+    }
+    if tokens.is_empty() && !peer.ip().to_canonical().is_loopback() {
+        tracing::warn!("Rejected reload from {peer}, only allowed from the loopback interface without authentication");
+        return (
+            StatusCode::FORBIDDEN,
+            "Reloading is only allowed from the loopback interface when authentication is disabled",
+        )
+            .into_response();
+    }
+
+    tracing::info!("Reloading the configuration, requested by {peer}");
+    let reloader = admin.reloader.clone();
+    match tokio::task::spawn_blocking(move || reloader.reload()).await {
+        Ok(Ok(report)) => {
+            let body = serde_json::json!({
+                "applied": report.applied,
+                "restart_required": report.restart_required,
+            });
+            (
+                [(header::CONTENT_TYPE, "application/json")],
+                format!("{body}\n"),
+            )
+                .into_response()
+        }
+        Ok(Err(err)) => {
+            tracing::error!("Can't reload the configuration: {err}");
+            (StatusCode::BAD_REQUEST, format!("{err}\n")).into_response()
+        }
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, format!("{err}\n")).into_response(),
+    }
+}
@@ -0,0 +1,428 @@
+use axum::extract::ws::close_code;
+use axum::http::{HeaderMap, HeaderValue};
+use burn::backend::ir::BackendIr;
+use burn::tensor::Device;
+use burn_communication::{
+    data_service::{TensorDataServer, TensorDataService},
+    CommunicationChannel, Message, ProtocolServer,
+};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{field, Span};
+
+use crate::placement::{Placement, DEVICE_HEADER};
+
+use super::health;
+use super::metrics::{self, CloseReason, ErrorKind};
+use super::placement::{Assignment, Placer};
+use super::session::{SessionExpiry, SessionManager};
+use super::task::{ComputeTask, Task};
+use super::version;
+use super::websocket::{ServerProtocol, WsServer, WsServerChannel, WsServerError};
+
+/// A device served by the server.
+pub struct ServedDevice<B: BackendIr> {
+    /// Name of the device in logs and metrics, e.g. `cuda:1`.
+    pub name: String,
+    pub device: Device<B>,
+}
+
+/// Serve remote backend clients on the given [server](WsServer) until the shutdown token is
+/// cancelled.
+///
+/// When `nested`, every device is served under `/device/<n>`, `n` being its position, each with
+/// its own sessions, and the sessions connecting to the root are placed on the devices according
+/// to the [policy](Placement). Otherwise the first device is served at the root. The tensor data
+/// service is shared by the devices.
+///
+/// Once every connection is closed, the sessions are dropped and the devices synchronized so
+/// that their memory is released before returning.
+pub async fn serve<B: BackendIr>(
+    devices: Vec<ServedDevice<B>>,
+    nested: bool,
+    placement: Placement,
+    server: WsServer,
+    shutdown: CancellationToken,
+) -> std::io::Result<()> {
+    let data_cancel_token = CancellationToken::new();
+    let data_service = Arc::new(TensorDataService::<B, ServerProtocol>::new(
+        data_cancel_token.clone(),
+    ));
+    let metrics = server.metrics();
+    let settings = server.settings();
+    let session_managers = devices
+        .iter()
+        .map(|served| {
+            Arc::new(SessionManager::<B>::new(
+                served.device.clone(),
+                data_service.clone(),
+                metrics.clone(),
+                settings.clone(),
+            ))
+        })
+        .collect();
+    let placer = Arc::new(Placer::new(placement, &devices, session_managers));
+
+    let mut server = server
+        .merge(health::router::<B>(&devices, nested))
+        .merge(metrics::router::<B>(&devices, nested, metrics))
+        .merge(version::router::<B>(&devices, nested));
+    let mut prefixes = vec![(String::new(), (!nested).then_some(0))];
+    if nested {
+        prefixes.extend(
+            (0..devices.len()).map(|position| (format!("/device/{position}"), Some(position))),
+        );
+    }
+    for (prefix, position) in prefixes {
+        server = route_device(
+            server.prefixed(&prefix),
+            placer.clone(),
+            position,
+            nested,
+            data_service.clone(),
+        )
+        .prefixed("");
+    }
+
+    let result = server
+        .serve(shutdown.cancelled_owned())
+        .await
+        .map_err(std::io::Error::other);
+
+    tracing::info!("Releasing device resources");
+    data_cancel_token.cancel();
+    core::mem::drop(placer);
+    core::mem::drop(data_service);
+    for served in &devices {
+        if let Err(err) = B::sync(&served.device) {
+            tracing::error!("Failed to sync device {} on shutdown: {err}", served.name);
+        }
+    }
+
+    result
+}
+
+/// Route the WebSocket connections of the sessions and tensor transfers of the device at the
+/// position, or of the sessions placed by the placer without one.
+///
+/// When `nested`, the response to the connection request of a requester carries the name of its
+/// device in the [`DEVICE_HEADER`] header.
+fn route_device<B: BackendIr>(
+    server: WsServer,
+    placer: Arc<Placer<B>>,
+    position: Option<usize>,
+    nested: bool,
+    data_service: Arc<TensorDataService<B, ServerProtocol>>,
+) -> WsServer {
+    server
+        .route("/response", {
+            let placer = placer.clone();
+            move |stream| handle_socket_response(placer, position, stream)
+        })
+        .route_with(
+            "/request",
+            move || {
+                let placer = placer.clone();
+                async move {
+                    let assignment = match position {
+                        Some(position) => placer.assign(position),
+                        None => placer.place().await,
+                    };
+                    let mut headers = HeaderMap::new();
+                    if nested {
+                        if let Ok(name) = HeaderValue::from_str(assignment.name()) {
+                            headers.insert(DEVICE_HEADER, name);
+                        }
+                    }
+
+                    (assignment, headers)
+                }
+            },
+            handle_socket_request,
+        )
+        .route_tensor_data_service(data_service)
+}
+
+/// Send the responses of a session to its client, expiring the session when the connection is
+/// lost or the client stops answering.
+///
+/// Without a position, the session is served by the device its requester placed it on.
+async fn handle_socket_response<B: BackendIr>(
+    placer: Arc<Placer<B>>,
+    position: Option<usize>,
+    mut socket: WsServerChannel,
+) {
+    tracing::info!(
+        "[Response Handler] On new connection from {}.",
+        socket.peer()
+    );
+
+    let packet = socket.recv().await;
+    let msg = match packet {
+        Ok(Some(msg)) => msg,
+        Ok(None) => {
+            tracing::info!("Response stream closed");
+            return;
+        }
+        Err(e) => {
+            tracing::info!("Response stream error on init: {e:?}");
+            socket.metrics().error(ErrorKind::Connection);
+            return;
+        }
+    };
+
+    let id = match rmp_serde::from_slice::<Task>(&msg.data) {
+        Ok(Task::Init(session_id)) => session_id,
+        msg => {
+            tracing::error!("Message is not a valid initialization task {msg:?}");
+            socket.metrics().error(ErrorKind::Protocol);
+            return;
+        }
+    };
+    Span::current().record("session", field::display(id));
+
+    let closing = socket.closing();
+    let position = match position {
+        Some(position) => Some(position),
+        None => tokio::select! {
+            position = placer.pinned(id) => position,
+            _ = closing.cancelled() => return,
+        },
+    };
+    let Some(position) = position else {
+        let reason = format!("Session {id} was not placed on a device");
+        tracing::error!("{reason}");
+        socket.metrics().error(ErrorKind::Protocol);
+        let _ = socket.close_with(close_code::POLICY, &reason).await;
+        return;
+    };
+    let session_manager = placer.session_manager(position);
+
+    let registered = tokio::select! {
+        registered = session_manager.register_responder(id) => registered,
+        _ = closing.cancelled() => return,
+    };
+    let mut receiver = match registered {
+        Ok(receiver) => receiver,
+        Err(err) => {
+            tracing::error!("{err}");
+            socket.metrics().error(ErrorKind::Protocol);
+            let _ = socket.close_with(close_code::POLICY, &err).await;
+            return;
+        }
+    };
+
+    tracing::info!("Response handler connection active");
+
+    loop {
+        let callback = tokio::select! {
+            callback = receiver.recv() => callback,
+            incoming = socket.recv() => {
+                let reason = match incoming {
+                    // Clients don't send messages on this connection.
+                    Ok(Some(_)) => continue,
+                    // The requester closes the session on shutdown.
+                    Ok(None) if closing.is_cancelled() => break,
+                    Ok(None) => {
+                        tracing::info!("Response stream closed");
+                        CloseReason::Disconnected
+                    }
+                    Err(err @ WsServerError::Unresponsive) => {
+                        tracing::warn!("Closing session {id}: {err}");
+                        CloseReason::Unresponsive
+                    }
+                    Err(err) => {
+                        tracing::info!("Response stream error: {err:?}, Closing.");
+                        socket.metrics().error(ErrorKind::Connection);
+                        CloseReason::Disconnected
+                    }
+                };
+                session_manager.expire(id, reason).await;
+                break;
+            }
+        };
+        let Some(mut callback) = callback else {
+            break;
+        };
+        let Some(response) = callback.recv().await else {
+            continue;
+        };
+        let bytes = match rmp_serde::to_vec(&response) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                tracing::error!("Can't serialize response: {err}");
+                break;
+            }
+        };
+
+        if let Err(err) = socket.send(Message::new(bytes.into())).await {
+            tracing::info!("Response stream error: {err:?}, Closing.");
+            socket.metrics().error(ErrorKind::Connection);
+            break;
+        }
+    }
+}
+
+/// Execute the requests of a session, closing it when the client closes it, goes away, sends no
+/// request for the idle timeout, or when its responder expires it.
+async fn handle_socket_request<B: BackendIr>(
+    mut socket: WsServerChannel,
+    mut assignment: Assignment<B>,
+) {
+    tracing::info!(
+        "[Request Handler] On new connection from {}.",
+        socket.peer()
+    );
+    let session_manager = assignment.session_manager();
+    let mut session_id = None;
+    let started = Instant::now();
+    let mut operations: u64 = 0;
+    let mut expiry = None;
+    let closing = socket.closing();
+
+    let reason = loop {
+        let packet = tokio::select! {
+            // Requests received before the session expired are executed.
+            biased;
+            packet = socket.recv() => packet,
+            reason = expired(&expiry) => break reason,
+            timeout = idle(session_manager.idle_timeout().filter(|_| session_id.is_some())) => {
+                tracing::info!(
+                    "No request received for {}s, closing the session",
+                    timeout.as_secs_f64()
+                );
+                break CloseReason::Idle;
+            }
+        };
+        let msg = match packet {
+            Ok(Some(msg)) => msg,
+            Ok(None) => {
+                tracing::info!("Request stream closed");
+                break CloseReason::Disconnected;
+            }
+            Err(err @ WsServerError::Unresponsive) => {
+                tracing::warn!("Closing the session: {err}");
+                break CloseReason::Unresponsive;
+            }
+            Err(e) => {
+                tracing::info!("Request stream error: {e:?}, Closing.");
+                socket.metrics().error(ErrorKind::Connection);
+                break CloseReason::Disconnected;
+            }
+        };
+
+        let task = match rmp_serde::from_slice::<Task>(&msg.data) {
+            Ok(val) => val,
+            Err(err) => {
+                tracing::info!(
+                    "Only bytes message in the MessagePack format are supported {err:?}"
+                );
+                socket.metrics().error(ErrorKind::Protocol);
+                break CloseReason::Disconnected;
+            }
+        };
+
+        if let Task::Close(id) = task {
+            session_id = Some(id);
+            break CloseReason::Closed;
+        }
+
+        if let (None, Task::Init(id)) = (session_id, &task) {
+            assignment.pin(*id);
+            let admitted = tokio::select! {
+                admitted = session_manager.admit(*id) => admitted,
+                _ = closing.cancelled() => return,
+            };
+            if let Err(rejected) = admitted {
+                tracing::warn!("Rejected session {id}: {rejected}");
+                let _ = socket
+                    .close_with(close_code::AGAIN, &rejected.to_string())
+                    .await;
+                return;
+            }
+        }
+
+        let (stream, connection_id, task) =
+            match session_manager.stream(&mut session_id, task).await {
+                Ok(Some(val)) => val,
+                Ok(None) => {
+                    if let Some(id) = session_id {
+                        Span::current().record("session", field::display(id));
+                        expiry = session_manager.expiry(id).await;
+                    }
+                    tracing::info!("Ops session activated {session_id:?}");
+                    continue;
+                }
+                Err(err) => {
+                    tracing::error!("{err}");
+                    socket.metrics().error(ErrorKind::Protocol);
+                    break CloseReason::Disconnected;
+                }
+            };
+
+        match task {
+            ComputeTask::RegisterOperation(op) => {
+                operations += 1;
+                stream.register_operation(op).await;
+            }
+            ComputeTask::RegisterTensor(id, data) => {
+                stream.register_tensor(id, data).await;
+            }
+            ComputeTask::ReadTensor(tensor) => {
+                stream.read_tensor(connection_id, tensor).await;
+            }
+            ComputeTask::SyncBackend => {
+                stream.sync(connection_id).await;
+            }
+            ComputeTask::RegisterTensorRemote(tensor, new_id) => {
+                stream.register_tensor_remote(tensor, new_id).await;
+            }
+            ComputeTask::ExposeTensorRemote {
+                tensor,
+                count,
+                transfer_id,
+            } => {
+                stream
+                    .expose_tensor_remote(tensor, count, transfer_id)
+                    .await;
+            }
+            ComputeTask::Seed(seed) => {
+                stream.seed(seed).await;
+            }
+            ComputeTask::DTypeUsage(dtype) => stream.dtype_usage(connection_id, dtype).await,
+        }
+    };
+    let reason = if closing.is_cancelled() {
+        CloseReason::Shutdown
+    } else {
+        reason
+    };
+
+    session_manager.close(session_id, reason).await;
+    tracing::info!(
+        duration_secs = started.elapsed().as_secs_f64(),
+        operations,
+        %reason,
+        "Session closed"
+    );
+}
+
+/// Wait for the session to expire, forever before it is initialized.
+async fn expired(expiry: &Option<Arc<SessionExpiry>>) -> CloseReason {
+    match expiry {
+        Some(expiry) => expiry.expired().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Wait for the idle timeout and return it, forever without one.
+async fn idle(timeout: Option<Duration>) -> Duration {
+    match timeout {
+        Some(timeout) => {
+            tokio::time::sleep(timeout).await;
+            timeout
+        }
+        None => std::future::pending().await,
+    }
+}
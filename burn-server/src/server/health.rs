@@ -0,0 +1,98 @@
+use axum::{extract::State, http::StatusCode, routing::get, Router};
+use burn::backend::ir::BackendIr;
+use burn::tensor::Device;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::device;
+
+use super::base::ServedDevice;
+
+/// Time the readiness check has to run its operation on the device.
+const READY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// HTTP routes reporting the health of the server, served without authentication:
+///
+/// - `/healthz`: the process is alive and serving requests.
+/// - `/readyz`: every device is able to run a tiny operation.
+/// - `/device/<n>/readyz`, when `nested`: the device at this position is able to run a tiny
+///   operation.
+pub fn router<B: BackendIr>(devices: &[ServedDevice<B>], nested: bool) -> Router {
+    let readiness = |devices: &[ServedDevice<B>]| {
+        Arc::new(Readiness::<B> {
+            devices: devices
+                .iter()
+                .map(|served| (served.name.clone(), served.device.clone()))
+                .collect(),
+            running: Arc::new(Mutex::new(())),
+        })
+    };
+
+    let mut router = Router::new()
+        .route("/healthz", get(|| async { "ok\n" }))
+        .route("/readyz", get(ready::<B>).with_state(readiness(devices)));
+    if nested {
+        for (position, served) in devices.iter().enumerate() {
+            router = router.route(
+                &format!("/device/{position}/readyz"),
+                get(ready::<B>).with_state(readiness(std::slice::from_ref(served))),
+            );
+        }
+    }
+
+    router
+}
+
+struct Readiness<B: BackendIr> {
+    /// The checked devices and their names.
+    devices: Vec<(String, Device<B>)>,
+    /// Held while a check runs, so that checks don't pile up on a wedged device.
+    running: Arc<Mutex<()>>,
+}
+
+async fn ready<B: BackendIr>(State(state): State<Arc<Readiness<B>>>) -> (StatusCode, String) {
+    let Ok(running) = state.running.clone().try_lock_owned() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "not ready: the previous check is still running\n".to_string(),
+        );
+    };
+
+    let state = state.clone();
+    let check = tokio::task::spawn_blocking(move || {
+        let _running = running;
+        let several = state.devices.len() > 1;
+        state.devices.iter().try_for_each(|(name, device)| {
+            device::check::<B>(device).map_err(|reason| {
+                if several {
+                    format!("{name}: {reason}")
+                } else {
+                    reason
+                }
+            })
+        })
+    });
+
+    match tokio::time::timeout(READY_TIMEOUT, check).await {
+        Ok(Ok(Ok(()))) => (StatusCode::OK, "ready\n".to_string()),
+        Ok(Ok(Err(reason))) => {
+            tracing::error!("Readiness check failed: {reason}");
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("not ready: {reason}\n"),
+            )
+        }
+        Ok(Err(err)) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("not ready: {err}\n"),
+        ),
+        Err(_) => {
+            tracing::error!("Readiness check timed out after {READY_TIMEOUT:?}");
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("not ready: the device didn't respond within {READY_TIMEOUT:?}\n"),
+            )
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use burn::backend::ir::{OperationIr, TensorId, TensorIr, TensorStatus};
+use burn::tensor::backend::ExecutionError;
+use burn::tensor::TensorData;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+/// The device memory allocated on behalf of a session, estimated from the size of the tensors it
+/// holds, and checked against the memory quota of the session.
+///
+/// Once an allocation exceeds the quota, the operation allocating it isn't executed, which leaves
+/// the graph of the client inconsistent: the session stops executing operations, except for the
+/// ones releasing its tensors, and every read returns the error.
+///
+/// The tensors still held when the session closes are [released](Self::release_all) so that the
+/// server can free them.
+pub struct SessionMemory {
+    quota: Option<u64>,
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    tensors: HashMap<TensorId, (TensorIr, u64)>,
+    in_use: u64,
+    exceeded: Option<QuotaExceeded>,
+}
+
+/// An allocation was rejected because it exceeds the memory quota of the session.
+#[derive(Debug, Clone)]
+pub struct QuotaExceeded {
+    quota: u64,
+    in_use: u64,
+    requested: u64,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The session memory quota of {} is exceeded: allocating {} with {} in use",
+            ByteSize(self.quota),
+            ByteSize(self.requested),
+            ByteSize(self.in_use)
+        )
+    }
+}
+
+impl SessionMemory {
+    pub fn new(quota: Option<u64>) -> Self {
+        Self {
+            quota,
+            state: Mutex::new(MemoryState::default()),
+        }
+    }
+
+    /// Account for an operation before it is executed: its outputs are allocated, then the inputs
+    /// it consumes are released.
+    ///
+    /// Returns whether the operation may be executed, which is only the case of operations
+    /// releasing tensors once the quota was exceeded. The error is returned the first time the
+    /// quota is exceeded.
+    pub fn operation(&self, op: &OperationIr) -> Result<bool, QuotaExceeded> {
+        let mut state = self.lock();
+
+        if state.exceeded.is_some() {
+            // Only release tensors that exist, those of skipped operations don't.
+            return Ok(match op {
+                OperationIr::Drop(tensor) => state.release(tensor.id),
+                _ => false,
+            });
+        }
+
+        let allocated = op
+            .outputs()
+            .map(|tensor| (tensor.clone(), tensor_size(tensor)))
+            .collect::<Vec<_>>();
+        let requested = allocated.iter().map(|(_, bytes)| bytes).sum();
+        self.check(&mut state, requested)?;
+
+        for (tensor, bytes) in allocated {
+            state.allocate(tensor, bytes);
+        }
+        for input in op.inputs() {
+            if input.status == TensorStatus::ReadWrite {
+                state.release(input.id);
+            }
+        }
+        if let OperationIr::Drop(tensor) = op {
+            state.release(tensor.id);
+        }
+
+        Ok(true)
+    }
+
+    /// Account for a tensor uploaded by the client, returning whether it may be registered.
+    pub fn tensor(&self, id: TensorId, data: &TensorData) -> Result<bool, QuotaExceeded> {
+        let mut state = self.lock();
+        if state.exceeded.is_some() {
+            return Ok(false);
+        }
+
+        let bytes = data.bytes.len() as u64;
+        self.check(&mut state, bytes)?;
+        let tensor = TensorIr {
+            id,
+            shape: data.shape.clone(),
+            status: TensorStatus::ReadWrite,
+            dtype: data.dtype,
+        };
+        state.allocate(tensor, bytes);
+
+        Ok(true)
+    }
+
+    /// Release every tensor the session still holds, returning them with their total size.
+    pub fn release_all(&self) -> (Vec<TensorIr>, u64) {
+        let mut state = self.lock();
+        let bytes = std::mem::take(&mut state.in_use);
+        let tensors = state
+            .tensors
+            .drain()
+            .map(|(_, (tensor, _))| TensorIr {
+                status: TensorStatus::ReadWrite,
+                ..tensor
+            })
+            .collect();
+
+        (tensors, bytes)
+    }
+
+    /// The error returned by reads once the quota was exceeded.
+    pub fn error(&self) -> Option<ExecutionError> {
+        self.lock()
+            .exceeded
+            .as_ref()
+            .map(|exceeded| ExecutionError::WithContext {
+                reason: format!("{exceeded}, the session can't execute more operations"),
+            })
+    }
+
+    fn check(&self, state: &mut MemoryState, requested: u64) -> Result<(), QuotaExceeded> {
+        match self.quota {
+            Some(quota) if state.in_use + requested > quota => {
+                let exceeded = QuotaExceeded {
+                    quota,
+                    in_use: state.in_use,
+                    requested,
+                };
+                state.exceeded = Some(exceeded.clone());
+                Err(exceeded)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl MemoryState {
+    fn allocate(&mut self, tensor: TensorIr, bytes: u64) {
+        if let Some((_, previous)) = self.tensors.insert(tensor.id, (tensor, bytes)) {
+            self.in_use -= previous;
+        }
+        self.in_use += bytes;
+    }
+
+    /// Returns whether the tensor was held by the session.
+    fn release(&mut self, id: TensorId) -> bool {
+        match self.tensors.remove(&id) {
+            Some((_, bytes)) => {
+                self.in_use -= bytes;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn tensor_size(tensor: &TensorIr) -> u64 {
+    tensor.shape.num_elements() as u64 * tensor.dtype.size() as u64
+}
+
+/// A number of bytes, displayed with a binary unit.
+pub struct ByteSize(pub u64);
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+
+        let mut value = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+
+        write!(f, "{value:.2} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use burn::backend::ir::InitOperationIr;
+    use burn::tensor::{DType, Shape};
+
+    /// A tensor of `elements` f32, 4 bytes each.
+    fn tensor(id: u64, elements: usize) -> TensorIr {
+        TensorIr {
+            id: TensorId::new(id),
+            shape: Shape::new([elements]),
+            status: TensorStatus::ReadWrite,
+            dtype: DType::F32,
+        }
+    }
+
+    fn init(id: u64, elements: usize) -> OperationIr {
+        OperationIr::Init(InitOperationIr {
+            out: tensor(id, elements),
+        })
+    }
+
+    #[test]
+    fn allocations_within_the_quota() {
+        let memory = SessionMemory::new(Some(100));
+
+        assert!(memory.operation(&init(1, 20)).unwrap());
+        assert!(memory.operation(&OperationIr::Drop(tensor(1, 20))).unwrap());
+        assert!(memory.operation(&init(2, 25)).unwrap());
+        assert!(memory.error().is_none());
+    }
+
+    #[test]
+    fn allocation_over_the_quota_fails_the_session() {
+        let memory = SessionMemory::new(Some(100));
+        assert!(memory.operation(&init(1, 20)).unwrap());
+
+        let exceeded = memory.operation(&init(2, 10)).unwrap_err();
+        assert_eq!(
+            exceeded.to_string(),
+            "The session memory quota of 100 B is exceeded: allocating 40 B with 80 B in use"
+        );
+        assert!(memory.error().is_some());
+
+        // Only the tensors the session holds can still be released.
+        assert!(!memory.operation(&init(3, 1)).unwrap());
+        assert!(!memory.operation(&OperationIr::Drop(tensor(2, 10))).unwrap());
+        assert!(memory.operation(&OperationIr::Drop(tensor(1, 20))).unwrap());
+    }
+
+    #[test]
+    fn upload_over_the_quota_fails_the_session() {
+        let memory = SessionMemory::new(Some(16));
+        let data = TensorData::new(vec![0f32; 5], [5]);
+
+        assert!(memory.tensor(TensorId::new(1), &data).is_err());
+        assert!(memory.error().is_some());
+    }
+
+    #[test]
+    fn no_quota() {
+        let memory = SessionMemory::new(None);
+
+        assert!(memory.operation(&init(1, 1 << 30)).unwrap());
+    }
+}
@@ -0,0 +1,616 @@
+use axum::{http::header, response::IntoResponse, routing::get, Router};
+use burn::backend::ir::{BackendIr, OperationIr};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::device::{self, DeviceMemory};
+
+use super::base::ServedDevice;
+
+/// The `/metrics` route, served without authentication.
+///
+/// When `nested`, the memory of each device is labelled with the name of the device.
+pub fn router<B: BackendIr>(
+    devices: &[ServedDevice<B>],
+    nested: bool,
+    metrics: Arc<Metrics>,
+) -> Router {
+    let backend = B::name(&devices[0].device);
+    let devices = devices
+        .iter()
+        .map(|served| (nested.then(|| served.name.clone()), served.device.clone()))
+        .collect::<Vec<_>>();
+
+    Router::new().route(
+        "/metrics",
+        get(move || async move {
+            let memory = tokio::task::spawn_blocking(move || {
+                devices
+                    .iter()
+                    .filter_map(|(name, device)| Some((name.clone(), device::memory(device)?)))
+                    .collect::<Vec<_>>()
+            })
+            .await
+            .unwrap_or_default();
+
+            (
+                [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                metrics.render(&backend, &memory),
+            )
+                .into_response()
+        }),
+    )
+}
+
+/// Kinds of errors counted by the `burn_server_errors_total` metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ErrorKind {
+    /// A connection was rejected for lack of a valid token.
+    Auth,
+    /// A WebSocket connection failed while sending or receiving.
+    Connection,
+    /// A client sent a message the server doesn't understand.
+    Protocol,
+    /// An allocation exceeded the memory quota of a session.
+    Quota,
+    /// Reading a tensor back failed.
+    Read,
+    /// Synchronizing the backend failed.
+    Sync,
+    /// A connection was rejected because the client is built with an incompatible version.
+    Version,
+}
+
+impl ErrorKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Auth => "auth",
+            ErrorKind::Connection => "connection",
+            ErrorKind::Protocol => "protocol",
+            ErrorKind::Quota => "quota",
+            ErrorKind::Read => "read",
+            ErrorKind::Sync => "sync",
+            ErrorKind::Version => "version",
+        }
+    }
+}
+
+/// Reasons a session was closed, the `reason` label of the `burn_server_sessions_closed_total`
+/// and `burn_server_reclaimed_*` metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CloseReason {
+    /// The client closed the session.
+    Closed,
+    /// A connection of the session was lost.
+    Disconnected,
+    /// The client sent no request for the session idle timeout.
+    Idle,
+    /// The client didn't answer pings for the heartbeat timeout.
+    Unresponsive,
+    /// The server shut down.
+    Shutdown,
+}
+
+impl CloseReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CloseReason::Closed => "closed",
+            CloseReason::Disconnected => "disconnected",
+            CloseReason::Idle => "idle",
+            CloseReason::Unresponsive => "unresponsive",
+            CloseReason::Shutdown => "shutdown",
+        }
+    }
+}
+
+impl fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Counters describing the activity of the server, rendered in the Prometheus text format on
+/// `/metrics`.
+pub struct Metrics {
+    start_time: f64,
+    sessions_active: AtomicI64,
+    sessions_total: AtomicU64,
+    sessions_queued: AtomicI64,
+    sessions_rejected: AtomicU64,
+    sessions_closed: Mutex<BTreeMap<CloseReason, u64>>,
+    reclaimed: Mutex<BTreeMap<CloseReason, Reclaimed>>,
+    connections: Mutex<BTreeMap<String, RouteConnections>>,
+    received_bytes: AtomicU64,
+    sent_bytes: AtomicU64,
+    operations: Mutex<BTreeMap<(&'static str, String), u64>>,
+    tensor_reads: AtomicU64,
+    tensor_read_bytes: AtomicU64,
+    errors: Mutex<BTreeMap<ErrorKind, u64>>,
+}
+
+/// Tensors left by closed sessions and freed by the server.
+#[derive(Default)]
+struct Reclaimed {
+    tensors: u64,
+    bytes: u64,
+}
+
+#[derive(Default)]
+struct RouteConnections {
+    active: i64,
+    total: u64,
+}
+
+/// Keeps a connection counted as active until dropped.
+pub struct ConnectionGuard {
+    metrics: Arc<Metrics>,
+    route: String,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if let Some(connections) = lock(&self.metrics.connections).get_mut(&self.route) {
+            connections.active -= 1;
+        }
+    }
+}
+
+/// Keeps a session counted as queued until dropped.
+pub struct QueuedSessionGuard {
+    metrics: Arc<Metrics>,
+}
+
+impl Drop for QueuedSessionGuard {
+    fn drop(&mut self) {
+        self.metrics.sessions_queued.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let start_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs_f64())
+            .unwrap_or_default();
+
+        Self {
+            start_time,
+            sessions_active: AtomicI64::new(0),
+            sessions_total: AtomicU64::new(0),
+            sessions_queued: AtomicI64::new(0),
+            sessions_rejected: AtomicU64::new(0),
+            sessions_closed: Mutex::new(BTreeMap::new()),
+            reclaimed: Mutex::new(BTreeMap::new()),
+            connections: Mutex::new(BTreeMap::new()),
+            received_bytes: AtomicU64::new(0),
+            sent_bytes: AtomicU64::new(0),
+            operations: Mutex::new(BTreeMap::new()),
+            tensor_reads: AtomicU64::new(0),
+            tensor_read_bytes: AtomicU64::new(0),
+            errors: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn session_opened(&self) {
+        self.sessions_active.fetch_add(1, Ordering::Relaxed);
+        self.sessions_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn session_closed(&self, reason: CloseReason) {
+        self.sessions_active.fetch_sub(1, Ordering::Relaxed);
+        *lock(&self.sessions_closed).entry(reason).or_default() += 1;
+    }
+
+    /// Count the tensors a closed session left behind, freed by the server.
+    pub fn memory_reclaimed(&self, reason: CloseReason, tensors: usize, bytes: u64) {
+        let mut reclaimed = lock(&self.reclaimed);
+        let reclaimed = reclaimed.entry(reason).or_default();
+        reclaimed.tensors += tensors as u64;
+        reclaimed.bytes += bytes;
+    }
+
+    /// Count a session waiting for a free slot, queued until the returned guard is dropped.
+    pub fn session_queued(self: &Arc<Self>) -> QueuedSessionGuard {
+        self.sessions_queued.fetch_add(1, Ordering::Relaxed);
+
+        QueuedSessionGuard {
+            metrics: self.clone(),
+        }
+    }
+
+    pub fn session_rejected(&self) {
+        self.sessions_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a new connection on the route, active until the returned guard is dropped.
+    pub fn connection_opened(self: &Arc<Self>, route: &str) -> ConnectionGuard {
+        let mut connections = lock(&self.connections);
+        let route_connections = connections.entry(route.to_string()).or_default();
+        route_connections.active += 1;
+        route_connections.total += 1;
+
+        ConnectionGuard {
+            metrics: self.clone(),
+            route: route.to_string(),
+        }
+    }
+
+    pub fn received(&self, bytes: usize) {
+        self.received_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self, bytes: usize) {
+        self.sent_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn operation(&self, op: &OperationIr) {
+        let (category, name) = operation_labels(op);
+        *lock(&self.operations).entry((category, name)).or_default() += 1;
+    }
+
+    pub fn tensor_read(&self, bytes: usize) {
+        self.tensor_reads.fetch_add(1, Ordering::Relaxed);
+        self.tensor_read_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn error(&self, kind: ErrorKind) {
+        *lock(&self.errors).entry(kind).or_default() += 1;
+    }
+
+    /// Render the metrics in the Prometheus text exposition format, with the memory of the
+    /// devices reporting it, labelled with their name when given.
+    pub fn render(
+        &self,
+        backend: &str,
+        device_memory: &[(Option<String>, DeviceMemory)],
+    ) -> String {
+        let mut out = String::new();
+        // Writing to a string can't fail.
+        let _ = self.write(&mut out, backend, device_memory);
+
+        out
+    }
+
+    fn write(
+        &self,
+        out: &mut String,
+        backend: &str,
+        device_memory: &[(Option<String>, DeviceMemory)],
+    ) -> fmt::Result {
+        header(out, "burn_server_info", "gauge", "Version and backend of the server.")?;
+        writeln!(
+            out,
+            "burn_server_info{{version=\"{}\",backend=\"{backend}\"}} 1",
+            env!("CARGO_PKG_VERSION")
+        )?;
+
+        header(
+            out,
+            "burn_server_start_time_seconds",
+            "gauge",
+            "Start time of the server since the Unix epoch in seconds.",
+        )?;
+        writeln!(out, "burn_server_start_time_seconds {}", self.start_time)?;
+
+        header(
+            out,
+            "burn_server_sessions_active",
+            "gauge",
+            "Number of open client sessions.",
+        )?;
+        writeln!(
+            out,
+            "burn_server_sessions_active {}",
+            self.sessions_active.load(Ordering::Relaxed)
+        )?;
+
+        header(
+            out,
+            "burn_server_sessions_total",
+            "counter",
+            "Number of client sessions opened.",
+        )?;
+        writeln!(
+            out,
+            "burn_server_sessions_total {}",
+            self.sessions_total.load(Ordering::Relaxed)
+        )?;
+
+        header(
+            out,
+            "burn_server_sessions_queued",
+            "gauge",
+            "Number of new sessions waiting for a free slot.",
+        )?;
+        writeln!(
+            out,
+            "burn_server_sessions_queued {}",
+            self.sessions_queued.load(Ordering::Relaxed)
+        )?;
+
+        header(
+            out,
+            "burn_server_sessions_rejected_total",
+            "counter",
+            "Number of sessions rejected because the maximum number of sessions were open.",
+        )?;
+        writeln!(
+            out,
+            "burn_server_sessions_rejected_total {}",
+            self.sessions_rejected.load(Ordering::Relaxed)
+        )?;
+
+        header(
+            out,
+            "burn_server_sessions_closed_total",
+            "counter",
+            "Number of sessions closed by reason.",
+        )?;
+        for (reason, count) in lock(&self.sessions_closed).iter() {
+            writeln!(
+                out,
+                "burn_server_sessions_closed_total{{reason=\"{reason}\"}} {count}"
+            )?;
+        }
+
+        let reclaimed = lock(&self.reclaimed);
+        header(
+            out,
+            "burn_server_reclaimed_tensors_total",
+            "counter",
+            "Number of tensors left by closed sessions and freed by the server, by close reason.",
+        )?;
+        for (reason, reclaimed) in reclaimed.iter() {
+            writeln!(
+                out,
+                "burn_server_reclaimed_tensors_total{{reason=\"{reason}\"}} {}",
+                reclaimed.tensors
+            )?;
+        }
+        header(
+            out,
+            "burn_server_reclaimed_bytes_total",
+            "counter",
+            "Estimated bytes of the tensors left by closed sessions and freed by the server, by close reason.",
+        )?;
+        for (reason, reclaimed) in reclaimed.iter() {
+            writeln!(
+                out,
+                "burn_server_reclaimed_bytes_total{{reason=\"{reason}\"}} {}",
+                reclaimed.bytes
+            )?;
+        }
+        drop(reclaimed);
+
+        let connections = lock(&self.connections);
+        header(
+            out,
+            "burn_server_connections_active",
+            "gauge",
+            "Number of open WebSocket connections by route.",
+        )?;
+        for (route, connections) in connections.iter() {
+            writeln!(
+                out,
+                "burn_server_connections_active{{route=\"{route}\"}} {}",
+                connections.active
+            )?;
+        }
+        header(
+            out,
+            "burn_server_connections_total",
+            "counter",
+            "Number of WebSocket connections accepted by route.",
+        )?;
+        for (route, connections) in connections.iter() {
+            writeln!(
+                out,
+                "burn_server_connections_total{{route=\"{route}\"}} {}",
+                connections.total
+            )?;
+        }
+        drop(connections);
+
+        header(
+            out,
+            "burn_server_received_bytes_total",
+            "counter",
+            "Bytes received in WebSocket messages.",
+        )?;
+        writeln!(
+            out,
+            "burn_server_received_bytes_total {}",
+            self.received_bytes.load(Ordering::Relaxed)
+        )?;
+
+        header(
+            out,
+            "burn_server_sent_bytes_total",
+            "counter",
+            "Bytes sent in WebSocket messages.",
+        )?;
+        writeln!(
+            out,
+            "burn_server_sent_bytes_total {}",
+            self.sent_bytes.load(Ordering::Relaxed)
+        )?;
+
+        header(
+            out,
+            "burn_server_operations_total",
+            "counter",
+            "Number of operations registered by clients, by category and operation.",
+        )?;
+        for ((category, op), count) in lock(&self.operations).iter() {
+            writeln!(
+                out,
+                "burn_server_operations_total{{category=\"{category}\",op=\"{op}\"}} {count}"
+            )?;
+        }
+
+        header(
+            out,
+            "burn_server_tensor_reads_total",
+            "counter",
+            "Number of tensors read back by clients.",
+        )?;
+        writeln!(
+            out,
+            "burn_server_tensor_reads_total {}",
+            self.tensor_reads.load(Ordering::Relaxed)
+        )?;
+
+        header(
+            out,
+            "burn_server_tensor_read_bytes_total",
+            "counter",
+            "Bytes of tensor data read back by clients.",
+        )?;
+        writeln!(
+            out,
+            "burn_server_tensor_read_bytes_total {}",
+            self.tensor_read_bytes.load(Ordering::Relaxed)
+        )?;
+
+        header(
+            out,
+            "burn_server_errors_total",
+            "counter",
+            "Number of errors by kind.",
+        )?;
+        for (kind, count) in lock(&self.errors).iter() {
+            writeln!(
+                out,
+                "burn_server_errors_total{{kind=\"{}\"}} {count}",
+                kind.as_str()
+            )?;
+        }
+
+        if !device_memory.is_empty() {
+            let labels = |name: &Option<String>| match name {
+                Some(name) => format!("{{device=\"{name}\"}}"),
+                None => String::new(),
+            };
+
+            header(
+                out,
+                "burn_server_device_memory_in_use_bytes",
+                "gauge",
+                "Bytes of device memory used by tensors.",
+            )?;
+            for (name, memory) in device_memory {
+                writeln!(
+                    out,
+                    "burn_server_device_memory_in_use_bytes{} {}",
+                    labels(name),
+                    memory.in_use
+                )?;
+            }
+
+            header(
+                out,
+                "burn_server_device_memory_reserved_bytes",
+                "gauge",
+                "Bytes of device memory reserved by the memory pools.",
+            )?;
+            for (name, memory) in device_memory {
+                writeln!(
+                    out,
+                    "burn_server_device_memory_reserved_bytes{} {}",
+                    labels(name),
+                    memory.reserved
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) -> fmt::Result {
+    writeln!(out, "# HELP {name} {help}")?;
+    writeln!(out, "# TYPE {name} {kind}")
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// The category and name of an operation, e.g. `("float", "Matmul")`.
+///
+/// Custom operations are all named `Custom`: their id is chosen by the client, so it can neither
+/// be trusted in a label nor bounded in number.
+fn operation_labels(op: &OperationIr) -> (&'static str, String) {
+    match op {
+        OperationIr::BaseFloat(op) => ("base_float", variant_name(op)),
+        OperationIr::BaseInt(op) => ("base_int", variant_name(op)),
+        OperationIr::BaseBool(op) => ("base_bool", variant_name(op)),
+        OperationIr::NumericFloat(_, op) => ("numeric_float", variant_name(op)),
+        OperationIr::NumericInt(_, op) => ("numeric_int", variant_name(op)),
+        OperationIr::Bool(op) => ("bool", variant_name(op)),
+        OperationIr::Int(op) => ("int", variant_name(op)),
+        OperationIr::Float(_, op) => ("float", variant_name(op)),
+        OperationIr::Module(op) => ("module", variant_name(op)),
+        OperationIr::Init(_) => ("init", "Init".to_string()),
+        OperationIr::Custom(_) => ("custom", "Custom".to_string()),
+        OperationIr::Drop(_) => ("drop", "Drop".to_string()),
+        // Operations only present with some features of burn, e.g. distributed ones.
+        #[allow(unreachable_patterns)]
+        op => ("other", variant_name(op)),
+    }
+}
+
+/// The name of the enum variant of a value, read from the start of its [`Debug`](fmt::Debug)
+/// output without formatting the rest.
+fn variant_name(value: &dyn fmt::Debug) -> String {
+    struct VariantName(String);
+
+    impl Write for VariantName {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            match s.find(|c: char| !(c.is_alphanumeric() || c == '_')) {
+                Some(end) => {
+                    self.0.push_str(&s[..end]);
+                    // Stop formatting at the end of the name.
+                    Err(fmt::Error)
+                }
+                None => {
+                    self.0.push_str(s);
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    let mut name = VariantName(String::new());
+    let _ = write!(name, "{value:?}");
+
+    name.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use burn::backend::ir::CustomOpIr;
+
+    #[test]
+    fn custom_operations_share_a_label() {
+        let metrics = Metrics::new();
+        for id in ["a\"} 1\nforged 2", "b\\", "c"] {
+            metrics.operation(&OperationIr::Custom(CustomOpIr {
+                id: id.to_string(),
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+            }));
+        }
+
+        let rendered = metrics.render("ndarray", &[]);
+        assert!(rendered
+            .contains("burn_server_operations_total{category=\"custom\",op=\"Custom\"} 3\n"));
+        assert!(!rendered.contains("forged"));
+    }
+}
@@ -0,0 +1,21 @@
+//! The remote backend server, adapted from `burn::server` so that the listener, sessions and
+//! connections are under our control.
+
+mod admin;
+mod base;
+mod health;
+mod memory;
+mod metrics;
+mod placement;
+mod processor;
+mod session;
+mod settings;
+mod stream;
+mod task;
+mod version;
+mod websocket;
+
+pub(crate) use admin::router as admin_router;
+pub(crate) use base::{serve, ServedDevice};
+pub(crate) use settings::Settings;
+pub(crate) use websocket::WsServer;
@@ -0,0 +1,273 @@
+use burn::backend::ir::BackendIr;
+use burn::tensor::Device;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::device;
+use crate::placement::Placement;
+
+use super::base::ServedDevice;
+use super::session::SessionManager;
+use super::task::SessionId;
+
+/// Time the responder of a session connecting to the root waits for its requester to place the
+/// session on a device.
+const PLACEMENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Assigns the sessions to the served devices.
+///
+/// Sessions connecting to `/device/<n>` are assigned to that device, the ones connecting to the
+/// root of a server serving several devices are placed on the least loaded one according to the
+/// [policy](Placement). A placed session is pinned to its device from its initialization by the
+/// requester until the requester is closed, for the responder of the session to find it.
+pub struct Placer<B: BackendIr> {
+    policy: Placement,
+    devices: Vec<PlacedDevice<B>>,
+    pins: Mutex<HashMap<SessionId, usize>>,
+    pinned: Notify,
+    /// Held while choosing a device, so that concurrent sessions don't all pick the same one.
+    placing: tokio::sync::Mutex<()>,
+}
+
+struct PlacedDevice<B: BackendIr> {
+    name: String,
+    device: Device<B>,
+    session_manager: Arc<SessionManager<B>>,
+    /// Requesters connected to the device, initialized or not.
+    sessions: AtomicUsize,
+}
+
+/// The device of a session, held by its requester.
+pub struct Assignment<B: BackendIr> {
+    placer: Arc<Placer<B>>,
+    position: usize,
+    /// Whether the device was chosen by the placer, the session then being pinned to it.
+    placed: bool,
+    pinned: Option<SessionId>,
+}
+
+impl<B: BackendIr> Placer<B> {
+    /// A placer for the devices, each with its session manager.
+    pub fn new(
+        policy: Placement,
+        devices: &[ServedDevice<B>],
+        session_managers: Vec<Arc<SessionManager<B>>>,
+    ) -> Self {
+        Self {
+            policy,
+            devices: devices
+                .iter()
+                .zip(session_managers)
+                .map(|(served, session_manager)| PlacedDevice {
+                    name: served.name.clone(),
+                    device: served.device.clone(),
+                    session_manager,
+                    sessions: AtomicUsize::new(0),
+                })
+                .collect(),
+            pins: Mutex::new(HashMap::new()),
+            pinned: Notify::new(),
+            placing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// The session manager of the device at this position.
+    pub fn session_manager(&self, position: usize) -> Arc<SessionManager<B>> {
+        self.devices[position].session_manager.clone()
+    }
+
+    /// Assign a session to the device at this position.
+    pub fn assign(self: &Arc<Self>, position: usize) -> Assignment<B> {
+        self.devices[position]
+            .sessions
+            .fetch_add(1, Ordering::Relaxed);
+
+        Assignment {
+            placer: self.clone(),
+            position,
+            placed: false,
+            pinned: None,
+        }
+    }
+
+    /// Place a session on the least loaded device.
+    pub async fn place(self: &Arc<Self>) -> Assignment<B> {
+        let _placing = self.placing.lock().await;
+        let memory = self.memory_in_use().await;
+
+        let mut assignment = self.assign(self.least_loaded(&memory));
+        assignment.placed = true;
+
+        assignment
+    }
+
+    /// The position of the device with the least memory in use, for the devices reporting it,
+    /// then with the fewest sessions.
+    fn least_loaded(&self, memory_in_use: &[Option<u64>]) -> usize {
+        (0..self.devices.len())
+            .min_by_key(|&position| {
+                let sessions = self.devices[position].sessions.load(Ordering::Relaxed);
+                let in_use = memory_in_use.get(position).copied().flatten().unwrap_or(0);
+                (in_use, sessions, position)
+            })
+            .unwrap_or_default()
+    }
+
+    /// The device a session was placed on, waiting for its requester to initialize it.
+    pub async fn pinned(&self, session_id: SessionId) -> Option<usize> {
+        let pinned = async {
+            loop {
+                let notified = self.pinned.notified();
+                tokio::pin!(notified);
+                // Don't miss a pin between the check below and the wait.
+                notified.as_mut().enable();
+
+                if let Some(&position) = self.pins.lock().unwrap().get(&session_id) {
+                    return position;
+                }
+
+                notified.await;
+            }
+        };
+
+        tokio::time::timeout(PLACEMENT_TIMEOUT, pinned).await.ok()
+    }
+
+    /// The memory used by tensors on each device for the memory policy, `None` for the devices
+    /// that don't report it.
+    async fn memory_in_use(&self) -> Vec<Option<u64>> {
+        if self.policy != Placement::MemoryInUse || self.devices.len() < 2 {
+            return Vec::new();
+        }
+
+        let devices = self
+            .devices
+            .iter()
+            .map(|placed| placed.device.clone())
+            .collect::<Vec<_>>();
+        tokio::task::spawn_blocking(move || {
+            devices
+                .iter()
+                .map(|device| device::memory(device).map(|memory| memory.in_use))
+                .collect()
+        })
+        .await
+        .unwrap_or_default()
+    }
+}
+
+impl<B: BackendIr> Assignment<B> {
+    /// The name of the device.
+    pub fn name(&self) -> &str {
+        &self.placer.devices[self.position].name
+    }
+
+    /// The session manager of the device.
+    pub fn session_manager(&self) -> Arc<SessionManager<B>> {
+        self.placer.session_manager(self.position)
+    }
+
+    /// Pin the session initialized by the requester to the device it was placed on.
+    pub fn pin(&mut self, session_id: SessionId) {
+        if !self.placed || self.pinned.is_some() {
+            return;
+        }
+
+        self.placer
+            .pins
+            .lock()
+            .unwrap()
+            .insert(session_id, self.position);
+        self.pinned = Some(session_id);
+        self.placer.pinned.notify_waiters();
+        tracing::info!(
+            "Placed session {session_id} on {}, {} session(s) on the device",
+            self.name(),
+            self.placer.devices[self.position]
+                .sessions
+                .load(Ordering::Relaxed)
+        );
+    }
+}
+
+impl<B: BackendIr> Drop for Assignment<B> {
+    fn drop(&mut self) {
+        if let Some(session_id) = self.pinned {
+            self.placer.pins.lock().unwrap().remove(&session_id);
+        }
+        self.placer.devices[self.position]
+            .sessions
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(all(test, feature = "ndarray"))]
+mod tests {
+    use super::*;
+    use burn::backend::NdArray;
+    use burn_communication::data_service::TensorDataService;
+    use tokio_util::sync::CancellationToken;
+
+    use crate::server::metrics::Metrics;
+    use crate::server::settings::Settings;
+
+    fn placer(policy: Placement, count: usize) -> Arc<Placer<NdArray>> {
+        let devices = (0..count)
+            .map(|position| ServedDevice {
+                name: format!("cpu:{position}"),
+                device: Default::default(),
+            })
+            .collect::<Vec<ServedDevice<NdArray>>>();
+        let session_managers = devices
+            .iter()
+            .map(|served| {
+                Arc::new(SessionManager::new(
+                    served.device,
+                    Arc::new(TensorDataService::new(CancellationToken::new())),
+                    Arc::new(Metrics::new()),
+                    Arc::new(Settings::new(Vec::new(), Default::default())),
+                ))
+            })
+            .collect();
+
+        Arc::new(Placer::new(policy, &devices, session_managers))
+    }
+
+    #[tokio::test]
+    async fn sessions_are_placed_on_the_device_with_the_fewest() {
+        let placer = placer(Placement::Sessions, 3);
+
+        let first = placer.place().await;
+        let second = placer.place().await;
+        let third = placer.place().await;
+        assert_eq!(
+            [first.name(), second.name(), third.name()],
+            ["cpu:0", "cpu:1", "cpu:2"]
+        );
+
+        drop(second);
+        assert_eq!(placer.place().await.name(), "cpu:1");
+    }
+
+    #[tokio::test]
+    async fn devices_without_memory_reports_fall_back_to_the_fewest_sessions() {
+        let placer = placer(Placement::MemoryInUse, 2);
+
+        let _first = placer.assign(0);
+        assert_eq!(placer.place().await.name(), "cpu:1");
+    }
+
+    #[test]
+    fn least_memory_in_use_ranks_before_the_fewest_sessions() {
+        let placer = placer(Placement::MemoryInUse, 3);
+        let _sessions = [placer.assign(2), placer.assign(2), placer.assign(0)];
+
+        assert_eq!(placer.least_loaded(&[Some(300), Some(200), Some(100)]), 2);
+        // Equal memory in use, device 1 has no session.
+        assert_eq!(placer.least_loaded(&[Some(100), Some(100), Some(100)]), 1);
+        assert_eq!(placer.least_loaded(&[Some(100), Some(200), Some(100)]), 0);
+    }
+}
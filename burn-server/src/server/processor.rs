@@ -0,0 +1,164 @@
+use burn::backend::ir::{BackendIr, OperationIr, TensorId, TensorIr};
+use burn::backend::router::{Runner, RunnerClient};
+use burn::tensor::{DType, TensorData};
+use burn_communication::data_service::{TensorDataService, TensorTransferId};
+use std::sync::Arc;
+use tokio::sync::mpsc::Sender;
+use tracing::Instrument;
+
+use super::memory::{QuotaExceeded, SessionMemory};
+use super::metrics::{ErrorKind, Metrics};
+use super::task::{ConnectionId, TaskResponse, TaskResponseContent, TensorRemote};
+use super::websocket::ServerProtocol;
+
+pub type Callback<M> = Sender<M>;
+
+pub enum ProcessorTask {
+    RegisterOperation(Box<OperationIr>),
+    RegisterTensor(TensorId, TensorData),
+    RegisterTensorRemote(TensorRemote, TensorId),
+    ExposeTensorRemote {
+        tensor: TensorIr,
+        transfer_id: TensorTransferId,
+        count: u32,
+    },
+    ReadTensor(ConnectionId, TensorIr, Callback<TaskResponse>),
+    Sync(ConnectionId, Callback<TaskResponse>),
+    Seed(u64),
+    DTypeUsage(ConnectionId, DType, Callback<TaskResponse>),
+    Close,
+}
+
+/// Executes the compute tasks of a stream, in order, on its own task.
+///
+/// Allocations are accounted in the memory of the session, which stops the execution once its
+/// quota is exceeded.
+pub fn start<B: BackendIr>(
+    runner: Runner<B>,
+    data_service: Arc<TensorDataService<B, ServerProtocol>>,
+    metrics: Arc<Metrics>,
+    memory: Arc<SessionMemory>,
+) -> Sender<ProcessorTask> {
+    let (task_sender, mut task_rec) = tokio::sync::mpsc::channel(1);
+
+    let processor = async move {
+        let exceeded = |exceeded: QuotaExceeded| {
+            tracing::warn!("{exceeded}");
+            metrics.error(ErrorKind::Quota);
+        };
+
+        while let Some(item) = task_rec.recv().await {
+            match item {
+                ProcessorTask::RegisterOperation(op) => {
+                    metrics.operation(&op);
+                    match memory.operation(&op) {
+                        Ok(true) => runner.register_op(*op),
+                        Ok(false) => {}
+                        Err(err) => exceeded(err),
+                    }
+                }
+                ProcessorTask::Sync(id, callback) => {
+                    let result = match memory.error() {
+                        Some(err) => Err(err),
+                        None => runner.sync(),
+                    };
+                    if result.is_err() {
+                        metrics.error(ErrorKind::Sync);
+                    }
+                    let _ = callback
+                        .send(TaskResponse {
+                            content: TaskResponseContent::SyncBackend(result),
+                            id,
+                        })
+                        .await;
+                }
+                ProcessorTask::RegisterTensor(id, data) => {
+                    match memory.tensor(id, &data) {
+                        Ok(true) => runner.register_tensor_data_id(id, data),
+                        Ok(false) => {}
+                        Err(err) => exceeded(err),
+                    }
+                }
+                ProcessorTask::RegisterTensorRemote(remote_tensor, new_id) => {
+                    tracing::info!(
+                        "Registering remote tensor...(id: {:?})",
+                        remote_tensor.transfer_id
+                    );
+                    match data_service
+                        .download_tensor(remote_tensor.address, remote_tensor.transfer_id)
+                        .await
+                    {
+                        Some(data) => match memory.tensor(new_id, &data) {
+                            Ok(true) => runner.register_tensor_data_id(new_id, data),
+                            Ok(false) => {}
+                            Err(err) => exceeded(err),
+                        },
+                        None => tracing::error!(
+                            "Can't download remote tensor (id: {:?})",
+                            remote_tensor.transfer_id
+                        ),
+                    }
+                }
+                ProcessorTask::ExposeTensorRemote {
+                    tensor,
+                    transfer_id,
+                    count,
+                } => {
+                    tracing::info!("Exposing tensor: (id: {transfer_id:?})");
+                    if let Some(err) = memory.error() {
+                        tracing::error!("Can't expose tensor (id: {transfer_id:?}): {err}");
+                        continue;
+                    }
+                    match runner.read_tensor_async(tensor).await {
+                        Ok(data) => data_service.expose_data(data, count, transfer_id).await,
+                        Err(err) => {
+                            tracing::error!("Can't expose tensor (id: {transfer_id:?}): {err}");
+                            metrics.error(ErrorKind::Read);
+                        }
+                    }
+                }
+                ProcessorTask::ReadTensor(id, tensor, callback) => {
+                    let tensor = match memory.error() {
+                        Some(err) => Err(err),
+                        None => runner.read_tensor_async(tensor).await,
+                    };
+                    match &tensor {
+                        Ok(data) => metrics.tensor_read(data.bytes.len()),
+                        Err(_) => metrics.error(ErrorKind::Read),
+                    }
+                    let _ = callback
+                        .send(TaskResponse {
+                            content: TaskResponseContent::ReadTensor(tensor),
+                            id,
+                        })
+                        .await;
+                }
+                ProcessorTask::Close => {
+                    let device = runner.device();
+                    if let Err(err) = runner.sync() {
+                        tracing::error!("Failed to sync stream on close: {err}");
+                    }
+                    core::mem::drop(runner);
+                    if let Err(err) = B::sync(&device) {
+                        tracing::error!("Failed to sync device on close: {err}");
+                    }
+                    break;
+                }
+                ProcessorTask::Seed(seed) => runner.seed(seed),
+                ProcessorTask::DTypeUsage(id, dtype, callback) => {
+                    let result = runner.dtype_usage(dtype);
+                    let _ = callback
+                        .send(TaskResponse {
+                            content: TaskResponseContent::DTypeUsage(result),
+                            id,
+                        })
+                        .await;
+                }
+            }
+        }
+    };
+    // Log records of the processor belong to the session that started it.
+    tokio::spawn(processor.in_current_span());
+
+    task_sender
+}
@@ -0,0 +1,526 @@
+use burn::backend::ir::{BackendIr, OperationIr};
+use burn::backend::router::{Runner, RunnerClient};
+use burn::tensor::{Device, StreamId};
+use burn_communication::data_service::TensorDataService;
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::{
+    mpsc::{Receiver, Sender},
+    Mutex, Notify, OwnedSemaphorePermit, Semaphore,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::Limits;
+
+use super::memory::{ByteSize, SessionMemory};
+use super::metrics::{CloseReason, Metrics};
+use super::settings::Settings;
+use super::stream::Stream;
+use super::task::{ComputeTask, ConnectionId, SessionId, Task, TaskResponse};
+use super::websocket::ServerProtocol;
+
+/// Time the responder of a session waits for the requester to be admitted, on top of the
+/// session queue timeout.
+const ADMISSION_GRACE: Duration = Duration::from_secs(10);
+
+/// Slots of a server without a maximum number of sessions, more than can ever be open.
+const UNLIMITED: usize = u32::MAX as usize;
+
+/// A session manager control the creation of sessions.
+///
+/// Each session manages its own stream, spawning one task per stream to mimic the same behavior
+/// a native backend would have.
+///
+/// Sessions are admitted by their requester, up to the maximum number of sessions, while their
+/// responder waits for the admission. The limits are read from the [settings](Settings) when they
+/// apply, so that changes apply to the sessions admitted next.
+pub struct SessionManager<B: BackendIr> {
+    runner: Runner<B>,
+    sessions: Mutex<HashMap<SessionId, Session<B>>>,
+    data_service: Arc<TensorDataService<B, ServerProtocol>>,
+    metrics: Arc<Metrics>,
+    settings: Arc<Settings>,
+    /// One permit per session that may be open.
+    slots: Arc<Semaphore>,
+    slot_limit: Arc<std::sync::Mutex<SlotLimit>>,
+    admitted: Notify,
+}
+
+/// The maximum number of sessions the slots are sized for.
+struct SlotLimit {
+    /// No limit when `None`.
+    max_sessions: Option<usize>,
+    /// Slots to drop when open sessions close, after the maximum was lowered below the number of
+    /// open sessions.
+    owed: usize,
+}
+
+/// Ends a session from its responder, e.g. when the client stops answering pings, the requester
+/// closing the session once [expired](Self::expired).
+#[derive(Default)]
+pub struct SessionExpiry {
+    token: CancellationToken,
+    reason: OnceLock<CloseReason>,
+}
+
+impl SessionExpiry {
+    fn expire(&self, reason: CloseReason) {
+        let _ = self.reason.set(reason);
+        self.token.cancel();
+    }
+
+    /// Wait for the session to expire, returning why.
+    pub async fn expired(&self) -> CloseReason {
+        self.token.cancelled().await;
+
+        self.reason
+            .get()
+            .copied()
+            .unwrap_or(CloseReason::Disconnected)
+    }
+}
+
+/// A session was not admitted because the maximum number of sessions were open.
+#[derive(Debug)]
+pub struct SessionRejected {
+    max_sessions: usize,
+}
+
+impl fmt::Display for SessionRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The server is at capacity with {} session(s) open, try again later",
+            self.max_sessions
+        )
+    }
+}
+
+struct Session<B: BackendIr> {
+    runner: Runner<B>,
+    streams: HashMap<StreamId, Stream>,
+    sender: Sender<Receiver<TaskResponse>>,
+    receiver: Option<Receiver<Receiver<TaskResponse>>>,
+    data_service: Arc<TensorDataService<B, ServerProtocol>>,
+    metrics: Arc<Metrics>,
+    memory: Arc<SessionMemory>,
+    expiry: Arc<SessionExpiry>,
+    _slot: OwnedSemaphorePermit,
+}
+
+impl<B: BackendIr> SessionManager<B> {
+    pub fn new(
+        device: Device<B>,
+        data_service: Arc<TensorDataService<B, ServerProtocol>>,
+        metrics: Arc<Metrics>,
+        settings: Arc<Settings>,
+    ) -> Self {
+        let max_sessions = settings.limits().max_sessions;
+
+        Self {
+            runner: Runner::new(device),
+            sessions: Mutex::new(Default::default()),
+            data_service,
+            metrics,
+            settings,
+            slots: Arc::new(Semaphore::new(max_sessions.unwrap_or(UNLIMITED))),
+            slot_limit: Arc::new(std::sync::Mutex::new(SlotLimit {
+                max_sessions,
+                owed: 0,
+            })),
+            admitted: Notify::new(),
+        }
+    }
+
+    /// Admit the session, waiting up to the queue timeout for another session to close when the
+    /// maximum number of sessions are open.
+    pub async fn admit(&self, session_id: SessionId) -> Result<(), SessionRejected> {
+        if self.sessions.lock().await.contains_key(&session_id) {
+            return Ok(());
+        }
+
+        let limits = self.settings.limits();
+        self.resize_slots(limits.max_sessions);
+        let slot = self.acquire_slot(session_id, &limits).await?;
+
+        let mut sessions = self.sessions.lock().await;
+        self.register_session(&mut sessions, session_id, slot, &limits);
+        self.admitted.notify_waiters();
+
+        Ok(())
+    }
+
+    /// How long a session may go without requests before it is closed, never when `None`.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.settings.limits().session_idle_timeout
+    }
+
+    async fn acquire_slot(
+        &self,
+        session_id: SessionId,
+        limits: &Limits,
+    ) -> Result<OwnedSemaphorePermit, SessionRejected> {
+        if let Ok(slot) = self.slots.clone().try_acquire_owned() {
+            return Ok(slot);
+        }
+
+        let max_sessions = limits.max_sessions.unwrap_or(UNLIMITED);
+        let queue_timeout = limits.session_queue_timeout;
+        if !queue_timeout.is_zero() {
+            tracing::info!("Session {session_id} is queued, {max_sessions} session(s) are open");
+            let _queued = self.metrics.session_queued();
+            let slot = tokio::time::timeout(queue_timeout, self.slots.clone().acquire_owned());
+            if let Ok(Ok(slot)) = slot.await {
+                return Ok(slot);
+            }
+        }
+
+        self.metrics.session_rejected();
+        Err(SessionRejected { max_sessions })
+    }
+
+    /// Size the slots for a new maximum number of sessions.
+    ///
+    /// When it shrinks below the number of open sessions, the slots of the sessions closing next
+    /// are dropped until the number of open sessions fits.
+    fn resize_slots(&self, max_sessions: Option<usize>) {
+        let mut limit = self.slot_limit.lock().unwrap();
+        if limit.max_sessions == max_sessions {
+            return;
+        }
+        let (from, to) = (
+            limit.max_sessions.unwrap_or(UNLIMITED),
+            max_sessions.unwrap_or(UNLIMITED),
+        );
+        limit.max_sessions = max_sessions;
+
+        if to > from {
+            // Slots still owed are forgiven first.
+            let forgiven = limit.owed.min(to - from);
+            limit.owed -= forgiven;
+            self.slots.add_permits(to - from - forgiven);
+            return;
+        }
+        let excess = from - to;
+        let missing = excess - self.slots.forget_permits(excess);
+        if missing == 0 {
+            return;
+        }
+        limit.owed += missing;
+
+        let (slots, slot_limit) = (self.slots.clone(), self.slot_limit.clone());
+        tokio::spawn(async move {
+            while let Ok(slot) = slots.clone().acquire_owned().await {
+                let mut limit = slot_limit.lock().unwrap();
+                if limit.owed > 0 {
+                    limit.owed -= 1;
+                    slot.forget();
+                }
+                if limit.owed == 0 {
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Register a new responder for the session once it is admitted. Only one responder can
+    /// exist for a session.
+    pub async fn register_responder(
+        &self,
+        session_id: SessionId,
+    ) -> Result<Receiver<Receiver<TaskResponse>>, String> {
+        tracing::info!("Register responder for session {session_id}");
+
+        let admitted = async {
+            loop {
+                let notified = self.admitted.notified();
+                tokio::pin!(notified);
+                // Don't miss an admission between the check below and the wait.
+                notified.as_mut().enable();
+
+                if let Some(session) = self.sessions.lock().await.get_mut(&session_id) {
+                    return session
+                        .receiver
+                        .take()
+                        .ok_or_else(|| format!("Session {session_id} already has a responder"));
+                }
+
+                notified.await;
+            }
+        };
+
+        let queue_timeout = self.settings.limits().session_queue_timeout;
+        tokio::time::timeout(queue_timeout + ADMISSION_GRACE, admitted)
+            .await
+            .unwrap_or_else(|_| Err(format!("Session {session_id} was not admitted")))
+    }
+
+    /// Get the stream for the current session and task.
+    ///
+    /// Returns `Ok(None)` when the task initialized the session and there is nothing to execute.
+    pub async fn stream(
+        &self,
+        session_id: &mut Option<SessionId>,
+        task: Task,
+    ) -> Result<Option<(Stream, ConnectionId, ComputeTask)>, String> {
+        let mut sessions = self.sessions.lock().await;
+
+        let session_id = match session_id {
+            Some(id) => *id,
+            None => match task {
+                Task::Init(id) if sessions.contains_key(&id) => {
+                    tracing::info!("Init requester for session {id}");
+                    *session_id = Some(id);
+                    return Ok(None);
+                }
+                Task::Init(id) => return Err(format!("Session {id} was not admitted")),
+                task => {
+                    return Err(format!(
+                        "The first message should initialize the session, got {task:?}"
+                    ))
+                }
+            },
+        };
+
+        let session = sessions
+            .get_mut(&session_id)
+            .ok_or_else(|| format!("Session {session_id} is not initialized"))?;
+        let (task, connection_id) = match task {
+            Task::Compute(task, connection_id) => (task, connection_id),
+            task => return Err(format!("Only compute tasks are supported, got {task:?}")),
+        };
+        let stream = session.select(connection_id.stream_id);
+
+        Ok(Some((stream, connection_id, task)))
+    }
+
+    /// The expiry of the session, to close it when its responder expires it.
+    pub async fn expiry(&self, session_id: SessionId) -> Option<Arc<SessionExpiry>> {
+        self.sessions
+            .lock()
+            .await
+            .get(&session_id)
+            .map(|session| session.expiry.clone())
+    }
+
+    /// Expire the session with the given id, its requester closing it.
+    pub async fn expire(&self, session_id: SessionId, reason: CloseReason) {
+        if let Some(session) = self.sessions.lock().await.get(&session_id) {
+            session.expiry.expire(reason);
+        }
+    }
+
+    /// Close the session with the given id, freeing the tensors it still holds.
+    pub async fn close(&self, session_id: Option<SessionId>, reason: CloseReason) {
+        if let Some(id) = session_id {
+            let session = self.sessions.lock().await.remove(&id);
+            if let Some(mut session) = session {
+                let (tensors, bytes) = session.close().await;
+                if tensors > 0 {
+                    tracing::info!(
+                        tensors,
+                        bytes,
+                        %reason,
+                        "Freed {tensors} tensor(s) ({}) left by session {id}",
+                        ByteSize(bytes)
+                    );
+                    self.metrics.memory_reclaimed(reason, tensors, bytes);
+                }
+                self.metrics.session_closed(reason);
+            }
+        }
+    }
+
+    fn register_session(
+        &self,
+        sessions: &mut HashMap<SessionId, Session<B>>,
+        id: SessionId,
+        slot: OwnedSemaphorePermit,
+        limits: &Limits,
+    ) {
+        // The slot is released right away when the session is already open.
+        sessions.entry(id).or_insert_with(|| {
+            tracing::info!("Creating a new session {id}");
+            self.metrics.session_opened();
+
+            Session::new(
+                self.runner.clone(),
+                self.data_service.clone(),
+                self.metrics.clone(),
+                Arc::new(SessionMemory::new(limits.session_memory_quota)),
+                slot,
+            )
+        });
+    }
+}
+
+impl<B: BackendIr> Session<B> {
+    fn new(
+        runner: Runner<B>,
+        data_service: Arc<TensorDataService<B, ServerProtocol>>,
+        metrics: Arc<Metrics>,
+        memory: Arc<SessionMemory>,
+        slot: OwnedSemaphorePermit,
+    ) -> Self {
+        let (sender, receiver) = tokio::sync::mpsc::channel(1);
+
+        Self {
+            runner,
+            streams: Default::default(),
+            sender,
+            receiver: Some(receiver),
+            data_service,
+            metrics,
+            memory,
+            expiry: Default::default(),
+            _slot: slot,
+        }
+    }
+
+    /// Select the current [stream](Stream) based on the given task.
+    fn select(&mut self, stream_id: StreamId) -> Stream {
+        self.streams
+            .entry(stream_id)
+            .or_insert_with(|| {
+                Stream::new(
+                    self.runner.clone(),
+                    self.sender.clone(),
+                    self.data_service.clone(),
+                    self.metrics.clone(),
+                    self.memory.clone(),
+                )
+            })
+            .clone()
+    }
+
+    /// Close all streams created in the session, then free the tensors the client didn't drop.
+    ///
+    /// Returns the number of tensors freed and their size.
+    async fn close(&mut self) -> (usize, u64) {
+        for (id, stream) in self.streams.drain() {
+            tracing::info!("Closing stream {id}");
+            stream.close().await;
+        }
+
+        let (tensors, bytes) = self.memory.release_all();
+        let count = tensors.len();
+        for tensor in tensors {
+            self.runner.register_op(OperationIr::Drop(tensor));
+        }
+
+        (count, bytes)
+    }
+}
+
+#[cfg(all(test, feature = "ndarray"))]
+mod tests {
+    use super::*;
+    use burn::backend::ir::TensorId;
+    use burn::backend::NdArray;
+    use burn::tensor::TensorData;
+
+    fn manager(limits: Limits) -> SessionManager<NdArray> {
+        SessionManager::new(
+            Default::default(),
+            Arc::new(TensorDataService::new(CancellationToken::new())),
+            Arc::new(Metrics::new()),
+            Arc::new(Settings::new(Vec::new(), limits)),
+        )
+    }
+
+    fn limits(max_sessions: usize, queue_timeout: Duration) -> Limits {
+        Limits {
+            max_sessions: Some(max_sessions),
+            session_queue_timeout: queue_timeout,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn second_session_is_rejected_at_capacity() {
+        let manager = manager(limits(1, Duration::ZERO));
+
+        manager.admit(SessionId::new(1)).await.unwrap();
+        assert!(manager.admit(SessionId::new(2)).await.is_err());
+        // Admitting an open session again doesn't take another slot.
+        manager.admit(SessionId::new(1)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn queued_session_is_admitted_when_another_closes() {
+        let manager = Arc::new(manager(limits(1, Duration::from_secs(10))));
+        manager.admit(SessionId::new(1)).await.unwrap();
+
+        let queued = tokio::spawn({
+            let manager = manager.clone();
+            async move { manager.admit(SessionId::new(2)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!queued.is_finished());
+
+        manager
+            .close(Some(SessionId::new(1)), CloseReason::Closed)
+            .await;
+        queued.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn queued_session_is_rejected_after_the_queue_timeout() {
+        let manager = manager(limits(1, Duration::from_millis(50)));
+        manager.admit(SessionId::new(1)).await.unwrap();
+
+        let rejected = manager.admit(SessionId::new(2)).await.unwrap_err();
+        assert_eq!(
+            rejected.to_string(),
+            "The server is at capacity with 1 session(s) open, try again later"
+        );
+    }
+
+    #[tokio::test]
+    async fn closing_a_session_frees_the_tensors_it_holds() {
+        let manager = manager(Limits::default());
+        let id = SessionId::new(1);
+        manager.admit(id).await.unwrap();
+
+        let data = TensorData::new(vec![1f32; 4], [4]);
+        let memory = manager.sessions.lock().await[&id].memory.clone();
+        manager
+            .runner
+            .register_tensor_data_id(TensorId::new(1), data.clone());
+        memory.tensor(TensorId::new(1), &data).unwrap();
+
+        manager.close(Some(id), CloseReason::Idle).await;
+
+        assert_eq!(memory.release_all().0.len(), 0);
+        let metrics = manager.metrics.render("ndarray", &[]);
+        assert!(metrics.contains("burn_server_sessions_closed_total{reason=\"idle\"} 1"));
+        assert!(metrics.contains("burn_server_reclaimed_tensors_total{reason=\"idle\"} 1"));
+        assert!(metrics.contains("burn_server_reclaimed_bytes_total{reason=\"idle\"} 16"));
+    }
+
+    #[tokio::test]
+    async fn expired_session_reports_why() {
+        let manager = manager(Limits::default());
+        let id = SessionId::new(1);
+        manager.admit(id).await.unwrap();
+
+        let expiry = manager.expiry(id).await.unwrap();
+        manager.expire(id, CloseReason::Unresponsive).await;
+
+        assert_eq!(expiry.expired().await, CloseReason::Unresponsive);
+    }
+
+    #[tokio::test]
+    async fn idle_timeout_follows_the_settings() {
+        let manager = manager(Limits::default());
+        assert_eq!(manager.idle_timeout(), None);
+
+        manager.settings.set_limits(Limits {
+            session_idle_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        });
+        assert_eq!(manager.idle_timeout(), Some(Duration::from_secs(5)));
+    }
+}
@@ -0,0 +1,41 @@
+use std::sync::{Arc, RwLock};
+
+use crate::auth::Token;
+use crate::config::Limits;
+
+/// The settings of a running server that can change without restarting it, on
+/// [reload](crate::ServerHandle::reload).
+///
+/// Connections and sessions read them when they need them, so that a change applies to the
+/// connections and sessions opened after it, and to the idle timeout of the open sessions.
+pub struct Settings {
+    tokens: RwLock<Arc<[Token]>>,
+    limits: RwLock<Limits>,
+}
+
+impl Settings {
+    pub fn new(tokens: Vec<Token>, limits: Limits) -> Self {
+        Self {
+            tokens: RwLock::new(tokens.into()),
+            limits: RwLock::new(limits),
+        }
+    }
+
+    /// The tokens clients may present, empty when authentication is disabled.
+    pub fn tokens(&self) -> Arc<[Token]> {
+        self.tokens.read().unwrap().clone()
+    }
+
+    /// The limits applied to client connections.
+    pub fn limits(&self) -> Limits {
+        self.limits.read().unwrap().clone()
+    }
+
+    pub fn set_tokens(&self, tokens: Vec<Token>) {
+        *self.tokens.write().unwrap() = tokens.into();
+    }
+
+    pub fn set_limits(&self, limits: Limits) {
+        *self.limits.write().unwrap() = limits;
+    }
+}
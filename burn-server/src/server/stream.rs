@@ -0,0 +1,108 @@
+use burn::backend::ir::{BackendIr, OperationIr, TensorId, TensorIr};
+use burn::backend::router::Runner;
+use burn::tensor::{DType, TensorData};
+use burn_communication::data_service::{TensorDataService, TensorTransferId};
+use std::sync::Arc;
+use tokio::sync::mpsc::{Receiver, Sender};
+
+use super::memory::SessionMemory;
+use super::metrics::Metrics;
+use super::processor::{self, ProcessorTask};
+use super::task::{ConnectionId, TaskResponse, TensorRemote};
+use super::websocket::ServerProtocol;
+
+/// A stream makes sure all operations registered are executed in the order they were sent to the
+/// server, potentially waiting to reconstruct consistency.
+#[derive(Clone)]
+pub struct Stream {
+    compute_sender: Sender<ProcessorTask>,
+    writer_sender: Sender<Receiver<TaskResponse>>,
+}
+
+impl Stream {
+    pub fn new<B: BackendIr>(
+        runner: Runner<B>,
+        writer_sender: Sender<Receiver<TaskResponse>>,
+        data_service: Arc<TensorDataService<B, ServerProtocol>>,
+        metrics: Arc<Metrics>,
+        memory: Arc<SessionMemory>,
+    ) -> Self {
+        Self {
+            compute_sender: processor::start(runner, data_service, metrics, memory),
+            writer_sender,
+        }
+    }
+
+    pub async fn register_operation(&self, op: Box<OperationIr>) {
+        self.compute(ProcessorTask::RegisterOperation(op)).await;
+    }
+
+    pub async fn register_tensor(&self, tensor_id: TensorId, data: TensorData) {
+        self.compute(ProcessorTask::RegisterTensor(tensor_id, data))
+            .await;
+    }
+
+    pub async fn register_tensor_remote(&self, tensor: TensorRemote, new_id: TensorId) {
+        self.compute(ProcessorTask::RegisterTensorRemote(tensor, new_id))
+            .await;
+    }
+
+    pub async fn expose_tensor_remote(
+        &self,
+        tensor: TensorIr,
+        count: u32,
+        transfer_id: TensorTransferId,
+    ) {
+        self.compute(ProcessorTask::ExposeTensorRemote {
+            tensor,
+            count,
+            transfer_id,
+        })
+        .await;
+    }
+
+    pub async fn read_tensor(&self, id: ConnectionId, desc: TensorIr) {
+        let (callback_sender, callback_rec) = tokio::sync::mpsc::channel(1);
+
+        self.compute(ProcessorTask::ReadTensor(id, desc, callback_sender))
+            .await;
+        self.respond(callback_rec).await;
+    }
+
+    pub async fn sync(&self, id: ConnectionId) {
+        let (callback_sender, callback_rec) = tokio::sync::mpsc::channel(1);
+
+        self.compute(ProcessorTask::Sync(id, callback_sender)).await;
+        self.respond(callback_rec).await;
+    }
+
+    /// Close the stream, waiting for its processor to execute the pending tasks and stop.
+    pub async fn close(&self) {
+        self.compute(ProcessorTask::Close).await;
+        self.compute_sender.closed().await;
+    }
+
+    pub async fn seed(&self, seed: u64) {
+        self.compute(ProcessorTask::Seed(seed)).await;
+    }
+
+    pub async fn dtype_usage(&self, id: ConnectionId, dtype: DType) {
+        let (callback_sender, callback_rec) = tokio::sync::mpsc::channel(1);
+
+        self.compute(ProcessorTask::DTypeUsage(id, dtype, callback_sender))
+            .await;
+        self.respond(callback_rec).await;
+    }
+
+    async fn compute(&self, task: ProcessorTask) {
+        if self.compute_sender.send(task).await.is_err() {
+            tracing::warn!("Stream processor is closed, dropping task");
+        }
+    }
+
+    async fn respond(&self, callback: Receiver<TaskResponse>) {
+        if self.writer_sender.send(callback).await.is_err() {
+            tracing::warn!("Response handler is closed, dropping response");
+        }
+    }
+}
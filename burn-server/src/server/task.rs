@@ -0,0 +1,89 @@
+//! Messages exchanged with `burn::backend::RemoteBackend` clients.
+//!
+//! These mirror the wire format of `burn-remote` (MessagePack, variants encoded by name), which
+//! keeps its own definitions private to the crate.
+//!
+//! Nothing checks that the copies still match, so `burn` and `burn-communication` are pinned to an
+//! exact version in `Cargo.toml`. Moving the pin goes together with reviewing these types against
+//! the new `burn-remote` and bumping [`PROTOCOL_REVISION`](crate::PROTOCOL_REVISION) when they
+//! changed.
+
+use burn::backend::ir::{OperationIr, TensorId, TensorIr};
+use burn::tensor::backend::{DTypeUsageSet, ExecutionError};
+use burn::tensor::{DType, StreamId, TensorData};
+use burn_communication::{data_service::TensorTransferId, Address};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+#[allow(missing_docs)]
+#[derive(Serialize, Deserialize, Debug, Hash, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+pub struct ConnectionId {
+    pub position: u64,
+    pub stream_id: StreamId,
+}
+
+/// Unique identifier that can represent a session.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize, PartialOrd, Ord)]
+pub struct SessionId {
+    id: u64,
+}
+
+impl Display for SessionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SessionId({})", self.id)
+    }
+}
+
+#[cfg(all(test, feature = "ndarray"))]
+impl SessionId {
+    pub fn new(id: u64) -> Self {
+        Self { id }
+    }
+}
+
+#[allow(missing_docs)]
+#[derive(Serialize, Deserialize, Debug)]
+pub enum Task {
+    Compute(ComputeTask, ConnectionId),
+    Init(SessionId),
+    Close(SessionId),
+}
+
+#[allow(missing_docs)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TensorRemote {
+    pub transfer_id: TensorTransferId,
+    pub address: Address,
+}
+
+#[allow(missing_docs)]
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ComputeTask {
+    Seed(u64),
+    RegisterOperation(Box<OperationIr>),
+    RegisterTensor(TensorId, TensorData),
+    RegisterTensorRemote(TensorRemote, TensorId),
+    ExposeTensorRemote {
+        tensor: TensorIr,
+        count: u32,
+        transfer_id: TensorTransferId,
+    },
+    ReadTensor(TensorIr),
+    SyncBackend,
+    DTypeUsage(DType),
+}
+
+#[allow(missing_docs)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TaskResponse {
+    pub content: TaskResponseContent,
+    pub id: ConnectionId,
+}
+
+#[allow(missing_docs)]
+#[derive(Serialize, Deserialize, Debug)]
+pub enum TaskResponseContent {
+    ReadTensor(Result<TensorData, ExecutionError>),
+    SyncBackend(Result<(), ExecutionError>),
+    DTypeUsage(DTypeUsageSet),
+}
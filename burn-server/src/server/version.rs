@@ -0,0 +1,35 @@
+use axum::{http::header, response::IntoResponse, routing::get, Router};
+use burn::backend::ir::BackendIr;
+
+use crate::version::{BURN_VERSION, COMPATIBLE_BURN_VERSIONS, PROTOCOL_REVISION};
+
+use super::base::ServedDevice;
+
+/// The `/version` route, served without authentication, reporting the versions of the server and
+/// the versions of burn its clients may use as JSON, and when `nested` the names of the devices
+/// served under `/device/<n>`, in order.
+pub fn router<B: BackendIr>(devices: &[ServedDevice<B>], nested: bool) -> Router {
+    let [(oldest_major, oldest_minor), (newest_major, newest_minor)] = COMPATIBLE_BURN_VERSIONS;
+    let mut body = serde_json::json!({
+        "server": env!("CARGO_PKG_VERSION"),
+        "burn": BURN_VERSION,
+        "protocol_revision": PROTOCOL_REVISION,
+        "compatible_burn": {
+            "oldest": format!("{oldest_major}.{oldest_minor}"),
+            "newest": format!("{newest_major}.{newest_minor}"),
+        },
+        "backend": B::name(&devices[0].device),
+    });
+    if nested {
+        let names = devices.iter().map(|served| served.name.as_str());
+        body["devices"] = names.collect::<Vec<_>>().into();
+    }
+    let body = format!("{body}\n");
+
+    Router::new().route(
+        "/version",
+        get(move || async move {
+            ([(header::CONTENT_TYPE, "application/json")], body).into_response()
+        }),
+    )
+}
@@ -0,0 +1,489 @@
+use axum::{
+    extract::{
+        ws::{self, WebSocket},
+        ConnectInfo, RawQuery, WebSocketUpgrade,
+    },
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::map_response,
+    response::{IntoResponse, Response},
+    routing::get,
+    serve::ListenerExt,
+    Router,
+};
+use burn_communication::{
+    websocket::WsClient, CommunicationChannel, CommunicationError, Message, Protocol,
+    ProtocolServer,
+};
+use futures::StreamExt;
+use socket2::{SockRef, TcpKeepalive};
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::Instrument;
+
+use crate::auth;
+use crate::config::ServerConfig;
+use crate::tls::TlsListener;
+use crate::version::{
+    self, Incompatible, BURN_VERSION, BURN_VERSION_HEADER, PROTOCOL_REVISION,
+    PROTOCOL_REVISION_HEADER, SERVER_VERSION_HEADER,
+};
+
+use super::metrics::{ConnectionGuard, ErrorKind, Metrics};
+use super::settings::Settings;
+
+/// The [protocol](Protocol) spoken by the server: WebSocket connections accepted on our own
+/// listener, and burn's WebSocket client to download tensors from other servers.
+#[derive(Clone)]
+pub struct ServerProtocol;
+
+impl Protocol for ServerProtocol {
+    type Client = WsClient;
+    type Server = WsServer;
+}
+
+/// A WebSocket server bound to a listener, routing each path to a handler.
+///
+/// Connection requests without one of the accepted [tokens](Settings::tokens) are rejected with
+/// `401 Unauthorized` before the upgrade, and the ones of clients reporting incompatible
+/// [versions](crate::version) with `400 Bad Request` and the reason. Every response carries the
+/// versions of the server.
+///
+/// Connections are kept alive with TCP keepalive probes sent every heartbeat interval, which the
+/// kernel of the client answers even when the client isn't reading. Reads fail once a peer stopped
+/// answering for the heartbeat timeout, e.g. a laptop that went to sleep, instead of waiting
+/// forever for its next message.
+///
+/// On shutdown the server stops accepting connections and waits up to the shutdown timeout for
+/// open connections to finish, then closes the remaining ones.
+pub struct WsServer {
+    listener: TcpListener,
+    router: Router,
+    /// Prepended to the paths of the WebSocket routes.
+    prefix: String,
+    settings: Arc<Settings>,
+    shutdown_timeout: Duration,
+    allow_unversioned_clients: bool,
+    tls: Option<TlsAcceptor>,
+    metrics: Arc<Metrics>,
+    connections: TaskTracker,
+    closing: CancellationToken,
+}
+
+/// A WebSocket connection accepted by the [server](WsServer).
+pub struct WsServerChannel {
+    inner: WebSocket,
+    peer: SocketAddr,
+    closing: CancellationToken,
+    metrics: Arc<Metrics>,
+    _connection: ConnectionGuard,
+}
+
+impl WsServer {
+    /// Bind the server to the given address, only accepting clients presenting one of the tokens
+    /// of the settings when any is set, and serving `wss://` when a TLS acceptor is given.
+    pub async fn bind(
+        address: SocketAddr,
+        config: &ServerConfig,
+        settings: Arc<Settings>,
+        tls: Option<TlsAcceptor>,
+    ) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address).await?;
+
+        Ok(Self {
+            listener,
+            router: Router::new(),
+            prefix: String::new(),
+            settings,
+            shutdown_timeout: config.shutdown_timeout,
+            allow_unversioned_clients: config.allow_unversioned_clients,
+            tls,
+            metrics: Arc::new(Metrics::new()),
+            connections: TaskTracker::new(),
+            closing: CancellationToken::new(),
+        })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// The metrics of the server, updated by its connections.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// The settings applied to client connections.
+    pub fn settings(&self) -> Arc<Settings> {
+        self.settings.clone()
+    }
+
+    /// Prepend the prefix, e.g. `/device/1`, to the paths of the WebSocket routes added next,
+    /// until another prefix is set.
+    pub fn prefixed(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.trim_end_matches('/').to_string();
+
+        self
+    }
+
+    /// Serve plain HTTP routes next to the WebSocket ones, without authentication.
+    pub fn merge(mut self, router: Router) -> Self {
+        self.router = self.router.merge(router);
+
+        self
+    }
+}
+
+impl WsServerChannel {
+    /// The address of the remote peer.
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// The metrics of the server.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Cancelled when the server closes the connection on shutdown.
+    pub fn closing(&self) -> CancellationToken {
+        self.closing.clone()
+    }
+
+    /// Close the connection with a [close code](ws::close_code) and a reason the client can
+    /// report.
+    pub async fn close_with(&mut self, code: u16, reason: &str) -> Result<(), WsServerError> {
+        self.inner
+            .send(ws::Message::Close(Some(ws::CloseFrame {
+                code,
+                reason: reason.into(),
+            })))
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl ProtocolServer for WsServer {
+    type Channel = WsServerChannel;
+    type Error = WsServerError;
+
+    async fn serve<F>(self, shutdown: F) -> Result<(), Self::Error>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let service = self
+            .router
+            .into_make_service_with_connect_info::<SocketAddr>();
+        let settings = self.settings.clone();
+        match self.tls {
+            Some(acceptor) => {
+                // Tapping the IO also gives access to the peer address of TLS connections.
+                let listener = TlsListener::new(self.listener, acceptor)?
+                    .tap_io(move |stream| keep_alive(stream.get_ref().0, &settings));
+                axum::serve(listener, service)
+                    .with_graceful_shutdown(shutdown)
+                    .await?
+            }
+            None => {
+                let listener = self
+                    .listener
+                    .tap_io(move |stream| keep_alive(stream, &settings));
+                axum::serve(listener, service)
+                    .with_graceful_shutdown(shutdown)
+                    .await?
+            }
+        }
+
+        self.connections.close();
+        if !self.connections.is_empty() {
+            tracing::info!(
+                "Waiting up to {:?} for {} open connection(s) to finish",
+                self.shutdown_timeout,
+                self.connections.len()
+            );
+        }
+        if tokio::time::timeout(self.shutdown_timeout, self.connections.wait())
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                "Closing {} connection(s) still open after {:?}",
+                self.connections.len(),
+                self.shutdown_timeout
+            );
+            self.closing.cancel();
+            self.connections.wait().await;
+        }
+
+        Ok(())
+    }
+
+    fn route<C, Fut>(self, path: &str, callback: C) -> Self
+    where
+        C: FnOnce(WsServerChannel) -> Fut + Clone + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.route_with(
+            path,
+            || async { ((), HeaderMap::new()) },
+            move |channel, ()| callback(channel),
+        )
+    }
+}
+
+impl WsServer {
+    /// Route the WebSocket connections to the path like [route](ProtocolServer::route), preparing
+    /// each accepted connection request before the upgrade.
+    ///
+    /// `prepare` runs once the client is authenticated and its versions checked. Its value is
+    /// given to the callback with the connection, and its headers are added to the response to the
+    /// connection request.
+    pub fn route_with<P, PFut, T, C, Fut>(mut self, path: &str, prepare: P, callback: C) -> Self
+    where
+        P: Fn() -> PFut + Clone + Send + Sync + 'static,
+        PFut: Future<Output = (T, HeaderMap)> + Send,
+        T: Send + 'static,
+        C: FnOnce(WsServerChannel, T) -> Fut + Clone + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let path = if path.starts_with('/') {
+            format!("{}{path}", self.prefix)
+        } else {
+            format!("{}/{path}", self.prefix)
+        };
+        let settings = self.settings.clone();
+        let allow_unversioned_clients = self.allow_unversioned_clients;
+        let connections = self.connections.clone();
+        let closing = self.closing.clone();
+        let metrics = self.metrics.clone();
+        let route = path.clone();
+
+        let method = get(
+            move |ws: WebSocketUpgrade,
+                  ConnectInfo(peer): ConnectInfo<SocketAddr>,
+                  headers: HeaderMap,
+                  RawQuery(query): RawQuery| async move {
+                let authorization = headers
+                    .get(header::AUTHORIZATION)
+                    .and_then(|value| value.to_str().ok());
+                if !auth::is_authorized(&settings.tokens(), authorization, query.as_deref()) {
+                    tracing::warn!("Rejected unauthenticated connection from {peer}");
+                    metrics.error(ErrorKind::Auth);
+                    return unauthorized();
+                }
+
+                let reported = |name| {
+                    headers
+                        .get(name)
+                        .map(|value| value.to_str().unwrap_or_default())
+                };
+                match version::check_client(
+                    reported(BURN_VERSION_HEADER),
+                    reported(PROTOCOL_REVISION_HEADER),
+                ) {
+                    Ok(()) => {}
+                    Err(Incompatible::Unversioned) if allow_unversioned_clients => {}
+                    Err(err) => {
+                        tracing::warn!("Rejected connection from {peer}: {err}");
+                        metrics.error(ErrorKind::Version);
+                        return (StatusCode::BAD_REQUEST, err.to_string()).into_response();
+                    }
+                }
+
+                let (prepared, prepared_headers) = prepare().await;
+
+                // The session is recorded by the handlers once the client sent its id.
+                let span = tracing::info_span!(
+                    "connection",
+                    %peer,
+                    route = %route,
+                    session = tracing::field::Empty
+                );
+                let limits = settings.limits();
+                let mut response = ws
+                    .max_message_size(limits.max_message_size)
+                    .max_frame_size(limits.max_frame_size)
+                    .on_upgrade(move |socket| {
+                        let channel = WsServerChannel {
+                            inner: socket,
+                            peer,
+                            closing,
+                            _connection: metrics.connection_opened(&route),
+                            metrics,
+                        };
+                        connections.track_future(callback(channel, prepared).instrument(span))
+                    });
+                response.headers_mut().extend(prepared_headers);
+
+                response
+            },
+        );
+
+        self.router = self
+            .router
+            .route(&path, method.layer(map_response(with_versions)));
+
+        self
+    }
+}
+
+/// Add the versions of the server to the response to a connection request.
+async fn with_versions(mut response: Response) -> Response {
+    let headers = response.headers_mut();
+    headers.insert(
+        SERVER_VERSION_HEADER,
+        HeaderValue::from_static(env!("CARGO_PKG_VERSION")),
+    );
+    headers.insert(BURN_VERSION_HEADER, HeaderValue::from_static(BURN_VERSION));
+    headers.insert(
+        PROTOCOL_REVISION_HEADER,
+        HeaderValue::from(PROTOCOL_REVISION),
+    );
+
+    response
+}
+
+/// Probe the peer of an idle connection every heartbeat interval, failing reads with a timeout
+/// once it stopped answering for the heartbeat timeout, or when data sent to it went
+/// unacknowledged that long.
+fn keep_alive(stream: &TcpStream, settings: &Settings) {
+    let limits = settings.limits();
+    let (interval, timeout) = (limits.heartbeat_interval, limits.heartbeat_timeout);
+    if interval.is_zero() {
+        return;
+    }
+
+    let keepalive = TcpKeepalive::new().with_time(interval);
+    #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+    let keepalive = keepalive.with_interval(interval);
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    let keepalive = keepalive.with_retries(
+        (timeout.as_secs_f64() / interval.as_secs_f64())
+            .ceil()
+            .max(1.0) as u32,
+    );
+
+    let socket = SockRef::from(stream);
+    let result = socket.set_tcp_keepalive(&keepalive);
+    #[cfg(target_os = "linux")]
+    let result = result.and_then(|_| socket.set_tcp_user_timeout(Some(timeout)));
+    if let Err(err) = result {
+        tracing::warn!("Can't enable TCP keepalive on a connection: {err}");
+    }
+}
+
+/// The response to a connection request without a valid token.
+pub(super) fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        "A valid token is required to connect to this server",
+    )
+        .into_response()
+}
+
+impl CommunicationChannel for WsServerChannel {
+    type Error = WsServerError;
+
+    async fn send(&mut self, message: Message) -> Result<(), WsServerError> {
+        self.metrics.sent(message.data.len());
+        self.inner.send(ws::Message::Binary(message.data)).await?;
+
+        Ok(())
+    }
+
+    /// Receive the next message, `None` when the peer closed the connection or the server is
+    /// closing it, telling the peer it is going away.
+    async fn recv(&mut self) -> Result<Option<Message>, WsServerError> {
+        loop {
+            let next = tokio::select! {
+                next = self.inner.next() => next,
+                _ = self.closing.cancelled() => {
+                    let _ = self
+                        .close_with(ws::close_code::AWAY, "The server is shutting down")
+                        .await;
+                    return Ok(None);
+                }
+            };
+
+            return match next {
+                Some(Ok(ws::Message::Binary(data))) => {
+                    self.metrics.received(data.len());
+                    Ok(Some(Message { data }))
+                }
+                Some(Ok(ws::Message::Close(_))) | None => Ok(None),
+                Some(Ok(ws::Message::Ping(_) | ws::Message::Pong(_))) => continue,
+                Some(Ok(msg)) => Err(WsServerError::UnknownMessage(format!("{msg:?}"))),
+                Some(Err(err)) if timed_out(&err) => Err(WsServerError::Unresponsive),
+                Some(Err(err)) => Err(WsServerError::Axum(err)),
+            };
+        }
+    }
+
+    async fn close(&mut self) -> Result<(), WsServerError> {
+        self.inner
+            .send(ws::Message::Close(Some(ws::CloseFrame {
+                code: ws::close_code::NORMAL,
+                reason: "Peer is closing".into(),
+            })))
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Whether the connection failed because the peer stopped answering keepalive probes.
+fn timed_out(err: &axum::Error) -> bool {
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<std::io::Error>() {
+            return err.kind() == std::io::ErrorKind::TimedOut;
+        }
+        source = err.source();
+    }
+
+    false
+}
+
+#[derive(Debug)]
+pub enum WsServerError {
+    Io(std::io::Error),
+    Axum(axum::Error),
+    UnknownMessage(String),
+    /// The peer stopped answering for the heartbeat timeout.
+    Unresponsive,
+}
+
+impl fmt::Display for WsServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Axum(err) => write!(f, "{err}"),
+            Self::UnknownMessage(msg) => write!(f, "Unknown message {msg}"),
+            Self::Unresponsive => write!(f, "The peer stopped answering"),
+        }
+    }
+}
+
+impl std::error::Error for WsServerError {}
+
+impl CommunicationError for WsServerError {}
+
+impl From<std::io::Error> for WsServerError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<axum::Error> for WsServerError {
+    fn from(err: axum::Error) -> Self {
+        Self::Axum(err)
+    }
+}
@@ -0,0 +1,226 @@
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Semaphore};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::WebPkiSupportedAlgorithms;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{
+    self, ClientConfig, DigitallySignedStruct, ServerConfig as RustlsConfig, SignatureScheme,
+};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+use crate::ServerError;
+
+/// Time a client has to complete the TLS handshake before the connection is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of completed handshakes waiting to be served.
+const ACCEPT_BACKLOG: usize = 64;
+
+/// Number of handshakes in progress beyond which new connections are dropped.
+const MAX_HANDSHAKES: usize = ACCEPT_BACKLOG;
+
+/// Certificate and private key the server presents to clients to serve `wss://`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// PEM file with the certificate chain, leaf certificate first.
+    pub cert: PathBuf,
+    /// PEM file with the private key of the certificate.
+    pub key: PathBuf,
+}
+
+impl TlsConfig {
+    /// Load the certificate and key.
+    pub(crate) fn acceptor(&self) -> Result<TlsAcceptor, ServerError> {
+        let certs = CertificateDer::pem_file_iter(&self.cert)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| tls_error(&self.cert, err))?;
+        if certs.is_empty() {
+            return Err(tls_error(&self.cert, "the file contains no certificate"));
+        }
+        let key = PrivateKeyDer::from_pem_file(&self.key).map_err(|err| tls_error(&self.key, err))?;
+
+        let config = RustlsConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|err| ServerError::Tls {
+            reason: format!(
+                "the key {} doesn't match the certificate {}: {err}",
+                self.key.display(),
+                self.cert.display()
+            ),
+        })?;
+
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+
+    /// A client configuration only trusting the configured certificate, used by health checks to
+    /// connect to the server through the loopback address, which the certificate usually doesn't
+    /// name.
+    pub(crate) fn pinned_client_config(&self) -> Result<ClientConfig, ServerError> {
+        let certificate = CertificateDer::pem_file_iter(&self.cert)
+            .and_then(|mut certs| certs.next().transpose())
+            .map_err(|err| tls_error(&self.cert, err))?
+            .ok_or_else(|| tls_error(&self.cert, "the file contains no certificate"))?;
+
+        let provider = rustls::crypto::ring::default_provider();
+        let verifier = PinnedCertificate {
+            certificate,
+            algorithms: provider.signature_verification_algorithms,
+        };
+
+        ClientConfig::builder_with_provider(Arc::new(provider))
+            .with_safe_default_protocol_versions()
+            .map(|builder| {
+                builder
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(verifier))
+                    .with_no_client_auth()
+            })
+            .map_err(|err| ServerError::Tls {
+                reason: err.to_string(),
+            })
+    }
+}
+
+fn tls_error(path: &Path, err: impl std::fmt::Display) -> ServerError {
+    ServerError::Tls {
+        reason: format!("can't load {}: {err}", path.display()),
+    }
+}
+
+/// A listener completing the TLS handshake of each accepted connection.
+///
+/// Handshakes run concurrently so that a slow or stalled client doesn't hold back the others, up
+/// to [`MAX_HANDSHAKES`] at a time so that a flood of connections can't exhaust the server.
+pub(crate) struct TlsListener {
+    local_addr: SocketAddr,
+    accepted: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+}
+
+impl TlsListener {
+    pub(crate) fn new(listener: TcpListener, acceptor: TlsAcceptor) -> std::io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let (sender, accepted) = mpsc::channel(ACCEPT_BACKLOG);
+        let handshakes = Arc::new(Semaphore::new(MAX_HANDSHAKES));
+
+        tokio::spawn(async move {
+            loop {
+                // Stop listening once the server drops the listener.
+                let accepted = tokio::select! {
+                    accepted = listener.accept() => accepted,
+                    _ = sender.closed() => break,
+                };
+                let (stream, peer) = match accepted {
+                    Ok(connection) => connection,
+                    Err(err) => {
+                        tracing::warn!("Failed to accept a connection: {err}");
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+
+                // Held until the connection is handed to the server.
+                let Ok(permit) = handshakes.clone().try_acquire_owned() else {
+                    tracing::warn!(
+                        "Dropped the connection from {peer}: {MAX_HANDSHAKES} TLS handshakes are \
+                         in progress"
+                    );
+                    continue;
+                };
+                let acceptor = acceptor.clone();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let _permit = permit;
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = sender.send((stream, peer)).await;
+                        }
+                        Ok(Err(err)) => tracing::warn!("TLS handshake with {peer} failed: {err}"),
+                        Err(_) => tracing::warn!("TLS handshake with {peer} timed out"),
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            accepted,
+        })
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.accepted.recv().await {
+            Some(connection) => connection,
+            // The accept loop only stops once this listener is dropped.
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// Accepts the server certificate only if it is exactly the pinned one.
+#[derive(Debug)]
+struct PinnedCertificate {
+    certificate: CertificateDer<'static>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if end_entity.as_ref() == self.certificate.as_ref() {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "the server certificate is not the configured one".to_string(),
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
@@ -0,0 +1,208 @@
+//! Versions of the server and of its clients, exchanged on the WebSocket handshake.
+//!
+//! Clients report the version of burn they are built with in the [`BURN_VERSION_HEADER`] header,
+//! and may report the revision of the protocol they speak in [`PROTOCOL_REVISION_HEADER`]. Burn's
+//! own client reports neither, so it is only accepted when the server
+//! [allows unversioned clients](crate::ServerConfig::allow_unversioned_clients). The server answers
+//! every handshake with its versions in the same headers, plus [`SERVER_VERSION_HEADER`].
+
+use std::fmt;
+
+/// Version of burn the server is built against.
+pub const BURN_VERSION: &str = env!("BURN_VERSION");
+
+/// Revision of the protocol spoken on `/request` and `/response`, bumped whenever its messages
+/// change in a way older clients can't follow.
+pub const PROTOCOL_REVISION: u32 = 1;
+
+/// Oldest and newest `major.minor` versions of burn whose remote clients speak the protocol of
+/// this server. Patch and pre-release versions don't matter.
+///
+/// Burn 0.20 encodes the scalars of operations differently, so its clients fail on the first
+/// operation with a scalar.
+pub const COMPATIBLE_BURN_VERSIONS: [(u64, u64); 2] = [(0, 21), (0, 21)];
+
+/// Header carrying the version of burn of the client, and of the server in responses.
+pub const BURN_VERSION_HEADER: &str = "x-burn-version";
+
+/// Header carrying the protocol revision of the client, and of the server in responses.
+pub const PROTOCOL_REVISION_HEADER: &str = "x-burn-protocol-revision";
+
+/// Header carrying the version of burn-server in responses.
+pub const SERVER_VERSION_HEADER: &str = "x-burn-server-version";
+
+/// The reason a client can't talk to this server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incompatible {
+    /// The client speaks another protocol revision.
+    Protocol(u32),
+    /// The client is built with a version of burn outside [`COMPATIBLE_BURN_VERSIONS`].
+    Burn(String),
+    /// A version header can't be parsed.
+    InvalidHeader { name: &'static str, value: String },
+    /// The client doesn't report its version of burn.
+    Unversioned,
+}
+
+impl fmt::Display for Incompatible {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Incompatible::Protocol(revision) => write!(
+                f,
+                "The client speaks protocol revision {revision}, this server doesn't ({})",
+                server_description()
+            ),
+            Incompatible::Burn(version) => {
+                let [(oldest_major, oldest_minor), newest] = COMPATIBLE_BURN_VERSIONS;
+                write!(
+                    f,
+                    "Clients built with burn {version} can't talk to this server ({}), use burn {oldest_major}.{oldest_minor}",
+                    server_description()
+                )?;
+                if newest != (oldest_major, oldest_minor) {
+                    write!(f, " to {}.{}", newest.0, newest.1)?;
+                }
+                Ok(())
+            }
+            Incompatible::InvalidHeader { name, value } => {
+                write!(f, "Invalid {name} header {value:?}")
+            }
+            Incompatible::Unversioned => write!(
+                f,
+                "The client doesn't report its version of burn in the {BURN_VERSION_HEADER} header, \
+                 so this server ({}) can't tell whether it speaks its protocol. Send the header, or \
+                 start the server with --allow-unversioned-clients to accept such clients, like \
+                 burn's own remote client",
+                server_description()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Incompatible {}
+
+/// Check the versions a client reported on its handshake.
+///
+/// A client reporting no version of burn is [`Incompatible::Unversioned`], even when its protocol
+/// revision matches.
+pub fn check_client(burn: Option<&str>, protocol: Option<&str>) -> Result<(), Incompatible> {
+    if let Some(value) = protocol {
+        let revision = value
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| Incompatible::InvalidHeader {
+                name: PROTOCOL_REVISION_HEADER,
+                value: value.to_string(),
+            })?;
+        if revision != PROTOCOL_REVISION {
+            return Err(Incompatible::Protocol(revision));
+        }
+    }
+
+    let value = burn.ok_or(Incompatible::Unversioned)?;
+    let version = major_minor(value).ok_or_else(|| Incompatible::InvalidHeader {
+        name: BURN_VERSION_HEADER,
+        value: value.to_string(),
+    })?;
+    let [oldest, newest] = COMPATIBLE_BURN_VERSIONS;
+    if version < oldest || version > newest {
+        return Err(Incompatible::Burn(value.trim().to_string()));
+    }
+
+    Ok(())
+}
+
+/// The version of the server, e.g. `burn-server 0.1.0, burn 0.21.0, protocol revision 1`.
+pub fn server_description() -> String {
+    format!(
+        "burn-server {}, burn {BURN_VERSION}, protocol revision {PROTOCOL_REVISION}",
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// The major and minor numbers of a version like `0.21.0-pre.1` or `0.20`.
+fn major_minor(version: &str) -> Option<(u64, u64)> {
+    let mut numbers = version.trim().splitn(3, '.');
+    let major = numbers.next()?.parse().ok()?;
+    let minor = numbers
+        .next()?
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()?;
+
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_versions() {
+        assert_eq!(check_client(Some("0.21.0"), Some("1")), Ok(()));
+    }
+
+    #[test]
+    fn missing_burn_version_is_unversioned() {
+        assert_eq!(check_client(None, None), Err(Incompatible::Unversioned));
+        assert_eq!(
+            check_client(None, Some("1")),
+            Err(Incompatible::Unversioned)
+        );
+        assert_eq!(
+            check_client(None, Some("2")),
+            Err(Incompatible::Protocol(2))
+        );
+    }
+
+    #[test]
+    fn missing_protocol_revision_is_accepted() {
+        assert_eq!(check_client(Some("0.21.0"), None), Ok(()));
+    }
+
+    #[test]
+    fn patch_and_pre_release_versions_are_compatible() {
+        for version in ["0.21", "0.21.4", "0.21.0-pre.1", " 0.21.1+local "] {
+            assert_eq!(check_client(Some(version), None), Ok(()), "{version}");
+        }
+    }
+
+    #[test]
+    fn other_minor_versions_are_rejected() {
+        assert_eq!(
+            check_client(Some("0.20.1"), Some("1")),
+            Err(Incompatible::Burn("0.20.1".to_string()))
+        );
+        assert_eq!(
+            check_client(Some("0.22.0"), None),
+            Err(Incompatible::Burn("0.22.0".to_string()))
+        );
+    }
+
+    #[test]
+    fn other_protocol_revisions_are_rejected() {
+        assert_eq!(
+            check_client(Some("0.21.0"), Some("2")),
+            Err(Incompatible::Protocol(2))
+        );
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected() {
+        assert_eq!(
+            check_client(Some("latest"), None),
+            Err(Incompatible::InvalidHeader {
+                name: BURN_VERSION_HEADER,
+                value: "latest".to_string(),
+            })
+        );
+        assert_eq!(
+            check_client(Some("0.21.0"), Some("one")),
+            Err(Incompatible::InvalidHeader {
+                name: PROTOCOL_REVISION_HEADER,
+                value: "one".to_string(),
+            })
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! Runs a burn-server in-process for the integration tests.
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+use burn::backend::remote::RemoteDevice;
+use burn_server::{Backend, ServerConfig, ServerHandle};
+
+/// A server running on the CPU on an ephemeral loopback port, stopped when dropped.
+pub struct TestServer {
+    handle: Option<ServerHandle>,
+}
+
+impl TestServer {
+    /// Start a server with the NdArray backend and the default limits.
+    pub fn start() -> Self {
+        Self::start_with(|_| {})
+    }
+
+    /// Start a server with the NdArray backend, changing the configuration first.
+    pub fn start_with(configure: impl FnOnce(&mut ServerConfig)) -> Self {
+        let mut config = Self::config();
+        configure(&mut config);
+
+        let handle = burn_server::spawn(config).expect("the server should start");
+
+        Self {
+            handle: Some(handle),
+        }
+    }
+
+    /// The configuration of the servers started by [`start`](Self::start).
+    pub fn config() -> ServerConfig {
+        ServerConfig {
+            port: 0,
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            backend: Some(Backend::NdArray),
+            // Clients keep their connection open for the lifetime of the process.
+            shutdown_timeout: Duration::from_millis(100),
+            // `RemoteDevice` doesn't report its version of burn.
+            allow_unversioned_clients: true,
+            log_filter: "error".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The `ws://` URL of the server.
+    pub fn url(&self) -> String {
+        self.handle().url()
+    }
+
+    /// A remote device connected to the server.
+    pub fn device(&self) -> RemoteDevice {
+        RemoteDevice::new(&self.url())
+    }
+
+    /// The handle of the running server.
+    pub fn handle(&self) -> &ServerHandle {
+        self.handle.as_ref().expect("the server is running")
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.shutdown();
+            let result = handle.join();
+            // Don't hide the failure of the test behind the one of the server.
+            if !std::thread::panicking() {
+                result.expect("the server should stop cleanly");
+            }
+        }
+    }
+}
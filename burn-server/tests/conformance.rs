@@ -0,0 +1,632 @@
+//! Compares the results of a catalog of tensor operations run on a `RemoteBackend` with the ones
+//! of the same operations run locally on the NdArray backend, from the same inputs.
+//!
+//! The operations run against the server at `REMOTE_BACKEND_URL` when it is set, on whichever
+//! backend it serves and which must allow unversioned clients (`--allow-unversioned-clients`),
+//! otherwise against an in-process server on the CPU. Each group of operations prints its report,
+//! the maximum absolute and relative error of each operation, which `cargo test` shows for failing
+//! groups, or for all of them with `--nocapture`.
+//!
+//! Floating point results match when `|remote - local| <= atol + rtol * |local|` for every
+//! element. The default tolerances depend on the operation, `CONFORMANCE_ATOL` and
+//! `CONFORMANCE_RTOL` replace them for every floating point operation. Integer and boolean
+//! results must match exactly.
+
+mod common;
+
+use std::fmt::Write;
+
+use burn::backend::remote::RemoteDevice;
+use burn::backend::{NdArray, RemoteBackend};
+use burn::tensor::activation::{gelu, relu, sigmoid, softmax};
+use burn::tensor::backend::Backend;
+use burn::tensor::module::{avg_pool2d, conv1d, conv2d, max_pool2d};
+use burn::tensor::ops::ConvOptions;
+use burn::tensor::{BasicOps, Bool, Device, IndexingUpdateOp, Int, Tensor, TensorData, TensorKind};
+
+use common::TestServer;
+
+type Reference = NdArray;
+
+/// How closely the remote results of an operation must match the local ones.
+#[derive(Clone, Copy)]
+enum Class {
+    /// Integer and boolean results, and floating point values that are only moved around.
+    Exact,
+    /// Floating point results computed from a few values per element.
+    Elementwise,
+    /// Floating point results accumulated over many values, whose rounding depends on the order
+    /// in which the backend sums them.
+    Accumulated,
+}
+
+#[derive(Clone, Copy)]
+struct Tolerance {
+    atol: f64,
+    rtol: f64,
+}
+
+impl Tolerance {
+    const EXACT: Self = Self {
+        atol: 0.0,
+        rtol: 0.0,
+    };
+
+    fn of(class: Class) -> Self {
+        let default = match class {
+            Class::Exact => return Self::EXACT,
+            Class::Elementwise => Self {
+                atol: 1e-6,
+                rtol: 1e-5,
+            },
+            Class::Accumulated => Self {
+                atol: 1e-5,
+                rtol: 1e-4,
+            },
+        };
+
+        Self {
+            atol: env_tolerance("CONFORMANCE_ATOL").unwrap_or(default.atol),
+            rtol: env_tolerance("CONFORMANCE_RTOL").unwrap_or(default.rtol),
+        }
+    }
+}
+
+fn env_tolerance(name: &str) -> Option<f64> {
+    let value = std::env::var(name).ok()?;
+    match value.parse() {
+        Ok(tolerance) if tolerance >= 0.0 => Some(tolerance),
+        _ => panic!("{name} should be a non-negative number, got {value:?}"),
+    }
+}
+
+/// The name, class and result of each operation of a group, in the order they ran.
+type Results = Vec<(&'static str, Class, TensorData)>;
+
+/// Collects the results of the operations of a group run on a device.
+struct Outputs<B: Backend> {
+    device: Device<B>,
+    results: Results,
+}
+
+impl<B: Backend> Outputs<B> {
+    fn new(device: Device<B>) -> Self {
+        Self {
+            device,
+            results: Vec::new(),
+        }
+    }
+
+    fn push<const D: usize, K>(&mut self, op: &'static str, class: Class, tensor: Tensor<B, D, K>)
+    where
+        K: TensorKind<B> + BasicOps<B>,
+    {
+        self.results.push((op, class, tensor.into_data()));
+    }
+
+    fn float<const D: usize>(&self, data: &TensorData) -> Tensor<B, D> {
+        Tensor::from_data(data.clone(), &self.device)
+    }
+
+    fn int<const D: usize>(&self, data: &TensorData) -> Tensor<B, D, Int> {
+        Tensor::from_data(data.clone(), &self.device)
+    }
+
+    fn bool<const D: usize>(&self, data: &TensorData) -> Tensor<B, D, Bool> {
+        Tensor::from_data(data.clone(), &self.device)
+    }
+}
+
+/// Deterministic inputs, identical on every run and for both backends.
+struct Inputs {
+    state: u64,
+}
+
+impl Inputs {
+    fn new() -> Self {
+        Self {
+            state: 0x9e37_79b9_7f4a_7c15,
+        }
+    }
+
+    /// Uniform values in `[low, high)`.
+    fn uniform<const D: usize>(&mut self, shape: [usize; D], low: f32, high: f32) -> TensorData {
+        let values = (0..shape.iter().product())
+            .map(|_| low + (high - low) * self.next_unit())
+            .collect::<Vec<_>>();
+        TensorData::new(values, shape)
+    }
+
+    /// Uniform integers in `[low, high)`.
+    fn ints<const D: usize>(&mut self, shape: [usize; D], low: i64, high: i64) -> TensorData {
+        let values = (0..shape.iter().product())
+            .map(|_| low + ((high - low) as f32 * self.next_unit()) as i64)
+            .collect::<Vec<_>>();
+        TensorData::new(values, shape)
+    }
+
+    fn bools<const D: usize>(&mut self, shape: [usize; D]) -> TensorData {
+        let values = (0..shape.iter().product())
+            .map(|_| self.next_unit() < 0.5)
+            .collect::<Vec<_>>();
+        TensorData::new(values, shape)
+    }
+
+    /// A value in `[0, 1)` from a xorshift generator.
+    fn next_unit(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+fn elementwise<B: Backend>(device: Device<B>) -> Results {
+    let mut inputs = Inputs::new();
+    let a = inputs.uniform([8, 16], -2.0, 2.0);
+    let b = inputs.uniform([8, 16], -2.0, 2.0);
+    let positive = inputs.uniform([8, 16], 0.1, 4.0);
+    let row = inputs.uniform([1, 16], -1.0, 1.0);
+
+    let mut out = Outputs::<B>::new(device);
+    let [a, b, positive, row] = [&a, &b, &positive, &row].map(|data| out.float::<2>(data));
+    use Class::Elementwise as E;
+
+    out.push("add", E, a.clone() + b.clone());
+    out.push("sub", E, a.clone() - b.clone());
+    out.push("mul", E, a.clone() * b.clone());
+    out.push("div", E, a.clone() / positive.clone());
+    out.push("add_broadcast", E, a.clone() + row.clone());
+    out.push("mul_scalar", E, a.clone().mul_scalar(1.5));
+    out.push("neg", E, a.clone().neg());
+    out.push("abs", E, a.clone().abs());
+    out.push("exp", E, a.clone().exp());
+    out.push("log", E, positive.clone().log());
+    out.push("log1p", E, positive.clone().log1p());
+    out.push("sqrt", E, positive.clone().sqrt());
+    out.push("recip", E, positive.clone().recip());
+    out.push("powf_scalar", E, positive.clone().powf_scalar(1.7));
+    out.push("powi_scalar", E, a.clone().powi_scalar(3));
+    out.push("sin", E, a.clone().sin());
+    out.push("cos", E, a.clone().cos());
+    out.push("tanh", E, a.clone().tanh());
+    out.push("erf", E, a.clone().erf());
+    out.push("floor", E, a.clone().floor());
+    out.push("clamp", E, a.clone().clamp(-0.5, 0.5));
+    out.push("relu", E, relu(a.clone()));
+    out.push("sigmoid", E, sigmoid(a.clone()));
+    out.push("gelu", E, gelu(a.clone()));
+    out.push("softmax", E, softmax(a.clone(), 1));
+
+    out.results
+}
+
+fn reductions<B: Backend>(device: Device<B>) -> Results {
+    let mut inputs = Inputs::new();
+    let a = inputs.uniform([32, 64], -1.0, 1.0);
+    let small = inputs.uniform([4, 4], 0.5, 1.5);
+
+    let mut out = Outputs::<B>::new(device);
+    let [a, small] = [&a, &small].map(|data| out.float::<2>(data));
+    use Class::{Accumulated as A, Exact as X};
+
+    out.push("sum", A, a.clone().sum());
+    out.push("mean", A, a.clone().mean());
+    out.push("prod", A, small.prod());
+    out.push("sum_dim", A, a.clone().sum_dim(1));
+    out.push("mean_dim", A, a.clone().mean_dim(0));
+    out.push("var", A, a.clone().var(1));
+    out.push("cumsum", A, a.clone().cumsum(1));
+    out.push("max", X, a.clone().max());
+    out.push("min", X, a.clone().min());
+    out.push("max_dim", X, a.clone().max_dim(1));
+    out.push("min_dim", X, a.clone().min_dim(0));
+    out.push("argmax", X, a.clone().argmax(1));
+    out.push("argmin", X, a.clone().argmin(0));
+    out.push("sort", X, a.clone().sort(1));
+
+    out.results
+}
+
+fn matmul<B: Backend>(device: Device<B>) -> Results {
+    let mut inputs = Inputs::new();
+    let a = inputs.uniform([64, 48], -1.0, 1.0);
+    let b = inputs.uniform([48, 32], -1.0, 1.0);
+    let batch_a = inputs.uniform([4, 16, 24], -1.0, 1.0);
+    let batch_b = inputs.uniform([4, 24, 8], -1.0, 1.0);
+    let column = inputs.uniform([48, 1], -1.0, 1.0);
+
+    let mut out = Outputs::<B>::new(device);
+    let [a, b, column] = [&a, &b, &column].map(|data| out.float::<2>(data));
+    let [batch_a, batch_b] = [&batch_a, &batch_b].map(|data| out.float::<3>(data));
+    use Class::Accumulated as A;
+
+    out.push("matmul", A, a.clone().matmul(b.clone()));
+    out.push("matmul_vector", A, a.clone().matmul(column));
+    out.push("matmul_transposed", A, b.transpose().matmul(a.transpose()));
+    out.push("matmul_batched", A, batch_a.matmul(batch_b));
+
+    out.results
+}
+
+fn conv<B: Backend>(device: Device<B>) -> Results {
+    let mut inputs = Inputs::new();
+    let signal = inputs.uniform([2, 3, 32], -1.0, 1.0);
+    let kernel_1d = inputs.uniform([4, 3, 5], -1.0, 1.0);
+    let image = inputs.uniform([2, 3, 16, 16], -1.0, 1.0);
+    let kernel_2d = inputs.uniform([4, 3, 3, 3], -1.0, 1.0);
+    let grouped_kernel = inputs.uniform([6, 1, 3, 3], -1.0, 1.0);
+    let bias = inputs.uniform([4], -1.0, 1.0);
+
+    let mut out = Outputs::<B>::new(device);
+    let [signal, kernel_1d] = [&signal, &kernel_1d].map(|data| out.float::<3>(data));
+    let [image, kernel_2d, grouped_kernel] =
+        [&image, &kernel_2d, &grouped_kernel].map(|data| out.float::<4>(data));
+    let bias = out.float::<1>(&bias);
+    use Class::{Accumulated as A, Exact as X};
+
+    out.push(
+        "conv1d",
+        A,
+        conv1d(
+            signal,
+            kernel_1d,
+            Some(bias.clone()),
+            ConvOptions::new([1], [2], [1], 1),
+        ),
+    );
+    out.push(
+        "conv2d",
+        A,
+        conv2d(
+            image.clone(),
+            kernel_2d.clone(),
+            Some(bias),
+            ConvOptions::new([1, 1], [1, 1], [1, 1], 1),
+        ),
+    );
+    out.push(
+        "conv2d_strided_dilated",
+        A,
+        conv2d(
+            image.clone(),
+            kernel_2d,
+            None,
+            ConvOptions::new([2, 2], [0, 0], [2, 2], 1),
+        ),
+    );
+    out.push(
+        "conv2d_grouped",
+        A,
+        conv2d(
+            image.clone().repeat_dim(1, 2),
+            grouped_kernel,
+            None,
+            ConvOptions::new([1, 1], [1, 1], [1, 1], 6),
+        ),
+    );
+    out.push(
+        "max_pool2d",
+        X,
+        max_pool2d(image.clone(), [2, 2], [2, 2], [0, 0], [1, 1], false),
+    );
+    out.push(
+        "avg_pool2d",
+        A,
+        avg_pool2d(image, [3, 3], [1, 1], [1, 1], true, false),
+    );
+
+    out.results
+}
+
+fn indexing<B: Backend>(device: Device<B>) -> Results {
+    let mut inputs = Inputs::new();
+    let a = inputs.uniform([6, 8], -1.0, 1.0);
+    let b = inputs.uniform([6, 8], -1.0, 1.0);
+    let patch = inputs.uniform([2, 3], -1.0, 1.0);
+    let rows = TensorData::new(vec![4i64, 0, 2, 2], [4]);
+    let gather = inputs.ints([6, 3], 0, 8);
+    let mask = inputs.bools([6, 8]);
+
+    let mut out = Outputs::<B>::new(device);
+    let [a, b, patch] = [&a, &b, &patch].map(|data| out.float::<2>(data));
+    let rows = out.int::<1>(&rows);
+    let gather = out.int::<2>(&gather);
+    let mask = out.bool::<2>(&mask);
+    use Class::{Elementwise as E, Exact as X};
+
+    out.push("slice", X, a.clone().slice([1..5, 2..7]));
+    out.push(
+        "slice_assign",
+        X,
+        a.clone().slice_assign([2..4, 1..4], patch),
+    );
+    out.push("narrow", X, a.clone().narrow(1, 3, 4));
+    out.push("select", X, a.clone().select(0, rows));
+    out.push("gather", X, a.clone().gather(1, gather.clone()));
+    out.push(
+        "scatter_add",
+        E,
+        a.clone().scatter(
+            1,
+            gather,
+            b.clone().slice([0..6, 0..3]),
+            IndexingUpdateOp::Add,
+        ),
+    );
+    out.push(
+        "mask_where",
+        X,
+        a.clone().mask_where(mask.clone(), b.clone()),
+    );
+    out.push("mask_fill", X, a.clone().mask_fill(mask, 0.25));
+    out.push("transpose", X, a.clone().transpose());
+    out.push(
+        "permute",
+        X,
+        a.clone().reshape([2, 3, 8]).permute([2, 0, 1]),
+    );
+    out.push("reshape", X, a.clone().reshape([4, 12]));
+    out.push("flip", X, a.clone().flip([0, 1]));
+    out.push("cat", X, Tensor::cat(vec![a.clone(), b], 0));
+    out.push("repeat_dim", X, a.clone().repeat_dim(1, 2));
+    out.push("expand", X, a.clone().slice([0..1, 0..8]).expand([3, 8]));
+    out.push("tril", X, a.tril(0));
+
+    out.results
+}
+
+fn casting<B: Backend>(device: Device<B>) -> Results {
+    let mut inputs = Inputs::new();
+    let a = inputs.uniform([8, 8], -40.0, 40.0);
+    let ints = inputs.ints([8, 8], -50, 50);
+    let mask = inputs.bools([8, 8]);
+
+    let mut out = Outputs::<B>::new(device);
+    let a = out.float::<2>(&a);
+    let ints = out.int::<2>(&ints);
+    let mask = out.bool::<2>(&mask);
+    use Class::Exact as X;
+
+    out.push("float_to_int", X, a.clone().int());
+    out.push("float_to_bool", X, a.clone().bool());
+    out.push("int_to_float", X, ints.clone().float());
+    out.push("int_to_bool", X, ints.bool());
+    out.push("bool_to_int", X, mask.clone().int());
+    out.push("bool_to_float", X, mask.float());
+    out.push("greater_elem", X, a.greater_elem(0.0));
+
+    out.results
+}
+
+fn int<B: Backend>(device: Device<B>) -> Results {
+    let mut inputs = Inputs::new();
+    let a = inputs.ints([8, 12], -100, 100);
+    let b = inputs.ints([8, 12], 1, 20);
+    let small = inputs.ints([12, 6], -10, 10);
+
+    let mut out = Outputs::<B>::new(device);
+    let [a, b, small] = [&a, &b, &small].map(|data| out.int::<2>(data));
+    use Class::Exact as X;
+
+    out.push("add", X, a.clone() + b.clone());
+    out.push("sub", X, a.clone() - b.clone());
+    out.push("mul", X, a.clone() * b.clone());
+    out.push("div", X, a.clone().abs() / b.clone());
+    out.push("remainder", X, a.clone().abs().remainder(b.clone()));
+    out.push("abs", X, a.clone().abs());
+    out.push("sign", X, a.clone().sign());
+    out.push("sum", X, a.clone().sum());
+    out.push("sum_dim", X, a.clone().sum_dim(1));
+    out.push("max_dim", X, a.clone().max_dim(0));
+    out.push("argmax", X, a.clone().argmax(1));
+    out.push("cumsum", X, a.clone().cumsum(0));
+    out.push("matmul", X, a.clone().clamp(-10, 10).matmul(small));
+    out.push("bitwise_and", X, a.clone().abs().bitwise_and(b.clone()));
+    out.push("equal", X, a.clone().remainder(b.clone()).equal_elem(0));
+    out.push("lower", X, a.lower(b));
+
+    out.results
+}
+
+fn bool<B: Backend>(device: Device<B>) -> Results {
+    let mut inputs = Inputs::new();
+    let a = inputs.bools([8, 12]);
+    let b = inputs.bools([8, 12]);
+
+    let mut out = Outputs::<B>::new(device);
+    let [a, b] = [&a, &b].map(|data| out.bool::<2>(data));
+    use Class::Exact as X;
+
+    out.push("not", X, a.clone().bool_not());
+    out.push("and", X, a.clone().bool_and(b.clone()));
+    out.push("or", X, a.clone().bool_or(b.clone()));
+    out.push("equal", X, a.clone().equal(b.clone()));
+    out.push("any", X, a.clone().any());
+    out.push("all", X, a.clone().all());
+    out.push("any_dim", X, a.clone().any_dim(1));
+    out.push("mask_fill", X, a.clone().int().mask_fill(b, 7));
+    out.push("transpose", X, a.transpose());
+
+    out.results
+}
+
+/// The errors of one operation.
+struct Comparison {
+    op: &'static str,
+    tolerance: Tolerance,
+    max_abs: f64,
+    max_rel: f64,
+    failure: Option<String>,
+}
+
+fn compare(op: &'static str, class: Class, remote: &TensorData, local: &TensorData) -> Comparison {
+    let tolerance = Tolerance::of(class);
+    let mut comparison = Comparison {
+        op,
+        tolerance,
+        max_abs: 0.0,
+        max_rel: 0.0,
+        failure: None,
+    };
+
+    if remote.shape != local.shape {
+        comparison.failure = Some(format!("shape {:?} != {:?}", remote.shape, local.shape));
+        return comparison;
+    }
+
+    // Integer dtypes may differ between backends, only the values are compared.
+    let pairs = remote.iter::<f64>().zip(local.iter::<f64>());
+    for (index, (actual, expected)) in pairs.enumerate() {
+        if actual.is_nan() && expected.is_nan() {
+            continue;
+        }
+        let abs = (actual - expected).abs();
+        let abs = if abs.is_nan() { f64::INFINITY } else { abs };
+        let rel = if expected != 0.0 {
+            abs / expected.abs()
+        } else if abs == 0.0 {
+            0.0
+        } else {
+            f64::INFINITY
+        };
+        comparison.max_abs = comparison.max_abs.max(abs);
+        comparison.max_rel = comparison.max_rel.max(rel);
+
+        if abs > tolerance.atol + tolerance.rtol * expected.abs() && comparison.failure.is_none() {
+            comparison.failure = Some(format!("element {index}: {actual} != {expected} (local)"));
+        }
+    }
+
+    comparison
+}
+
+/// Run a group of operations remotely and locally, print the report and fail with the operations
+/// exceeding their tolerance.
+fn check(
+    group: &str,
+    remote: fn(RemoteDevice) -> Results,
+    local: fn(Device<Reference>) -> Results,
+) {
+    let target = Target::connect();
+    let remote = remote(target.device());
+    let local = local(Default::default());
+
+    let comparisons = remote
+        .iter()
+        .zip(&local)
+        .map(|((op, class, remote), (_, _, local))| compare(op, *class, remote, local))
+        .collect::<Vec<_>>();
+    let report = report(group, &target.url(), &comparisons);
+    println!("{report}");
+
+    let failures = comparisons
+        .iter()
+        .filter(|comparison| comparison.failure.is_some())
+        .count();
+    assert!(
+        failures == 0,
+        "{failures} operation(s) of {group} don't match the reference:\n{report}"
+    );
+}
+
+fn report(group: &str, url: &str, comparisons: &[Comparison]) -> String {
+    let mut report = format!("{group} on {url} against NdArray\n");
+    let _ = writeln!(
+        report,
+        "{:<24} {:>12} {:>12} {:>10} {:>10}  result",
+        "op", "max abs", "max rel", "atol", "rtol"
+    );
+    for comparison in comparisons {
+        let Tolerance { atol, rtol } = comparison.tolerance;
+        let _ = writeln!(
+            report,
+            "{:<24} {:>12.3e} {:>12.3e} {:>10.1e} {:>10.1e}  {}",
+            comparison.op,
+            comparison.max_abs,
+            comparison.max_rel,
+            atol,
+            rtol,
+            comparison.failure.as_deref().unwrap_or("ok")
+        );
+    }
+
+    report
+}
+
+/// The server the operations run on, the one at `REMOTE_BACKEND_URL` or an in-process one.
+enum Target {
+    Url(String),
+    Local(TestServer),
+}
+
+impl Target {
+    fn connect() -> Self {
+        match std::env::var("REMOTE_BACKEND_URL") {
+            Ok(url) => Self::Url(url),
+            Err(_) => Self::Local(TestServer::start()),
+        }
+    }
+
+    fn url(&self) -> String {
+        match self {
+            Self::Url(url) => url.clone(),
+            Self::Local(server) => server.url(),
+        }
+    }
+
+    fn device(&self) -> RemoteDevice {
+        match self {
+            Self::Url(url) => RemoteDevice::new(url),
+            Self::Local(server) => server.device(),
+        }
+    }
+}
+
+#[test]
+fn conformance_elementwise() {
+    check(
+        "elementwise",
+        elementwise::<RemoteBackend>,
+        elementwise::<Reference>,
+    );
+}
+
+#[test]
+fn conformance_reductions() {
+    check(
+        "reductions",
+        reductions::<RemoteBackend>,
+        reductions::<Reference>,
+    );
+}
+
+#[test]
+fn conformance_matmul() {
+    check("matmul", matmul::<RemoteBackend>, matmul::<Reference>);
+}
+
+#[test]
+fn conformance_conv() {
+    check("conv", conv::<RemoteBackend>, conv::<Reference>);
+}
+
+#[test]
+fn conformance_indexing() {
+    check("indexing", indexing::<RemoteBackend>, indexing::<Reference>);
+}
+
+#[test]
+fn conformance_casting() {
+    check("casting", casting::<RemoteBackend>, casting::<Reference>);
+}
+
+#[test]
+fn conformance_int() {
+    check("int", int::<RemoteBackend>, int::<Reference>);
+}
+
+#[test]
+fn conformance_bool() {
+    check("bool", bool::<RemoteBackend>, bool::<Reference>);
+}
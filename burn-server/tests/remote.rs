@@ -0,0 +1,438 @@
+//! The operations of the `remote-client` example, run through a `RemoteDevice` against an
+//! in-process server on the CPU.
+
+mod common;
+
+use burn::backend::remote::RemoteDevice;
+use burn::backend::RemoteBackend;
+use burn::tensor::{Distribution, Tensor};
+use burn_server::{
+    check_health, Devices, HealthEndpoint, Placement, ReloadReport, ServerConfig, ServerError,
+    TlsConfig, Token,
+};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::Message;
+
+use common::TestServer;
+
+type B = RemoteBackend;
+
+const TOLERANCE: f32 = 1e-5;
+
+fn values(tensor: Tensor<B, 2>) -> Vec<f32> {
+    tensor
+        .into_data()
+        .to_vec()
+        .expect("the tensor should hold f32 values")
+}
+
+fn assert_approx_eq(actual: &[f32], expected: &[f32]) {
+    assert_eq!(actual.len(), expected.len(), "{actual:?} != {expected:?}");
+    for (actual_value, expected_value) in actual.iter().zip(expected) {
+        assert!(
+            (actual_value - expected_value).abs() <= TOLERANCE,
+            "{actual:?} != {expected:?}"
+        );
+    }
+}
+
+/// Send a raw HTTP request to the server, returning the head of the response, e.g. to check the
+/// status of a WebSocket handshake without upgrading the connection.
+fn http(server: &TestServer, request: &str) -> String {
+    let mut stream =
+        TcpStream::connect(server.handle().local_addr()).expect("the server should accept");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+
+    let mut response = Vec::new();
+    let mut buffer = [0; 1024];
+    while !response.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut buffer).expect("the server should respond") {
+            0 => break,
+            read => response.extend_from_slice(&buffer[..read]),
+        }
+    }
+
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+/// The `/metrics` of the server.
+fn metrics(server: &TestServer) -> String {
+    let mut stream =
+        TcpStream::connect(server.handle().local_addr()).expect("the server should accept");
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    response
+}
+
+/// A self-signed certificate for `localhost` and its key, written as PEM files to the directory.
+fn self_signed(dir: &Path, name: &str) -> TlsConfig {
+    let certified = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+    let tls = TlsConfig {
+        cert: dir.join(format!("{name}.pem")),
+        key: dir.join(format!("{name}.key")),
+    };
+    std::fs::write(&tls.cert, certified.cert.pem()).unwrap();
+    std::fs::write(&tls.key, certified.signing_key.serialize_pem()).unwrap();
+
+    tls
+}
+
+/// Relay the connections to a loopback port over TLS to the server, trusting only its
+/// certificate, returning the `ws://` URL of the relay.
+///
+/// A `RemoteDevice` only speaks `ws://`, so it reaches a `wss://` server through the relay.
+fn tls_relay(server: &TestServer, tls: &TlsConfig) -> String {
+    use tokio_rustls::rustls::pki_types::pem::PemObject;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+    use tokio_rustls::rustls::{self, ClientConfig, RootCertStore};
+
+    let mut roots = RootCertStore::empty();
+    roots
+        .add(CertificateDer::from_pem_file(&tls.cert).unwrap())
+        .unwrap();
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+
+    let server_addr = server.handle().local_addr();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let relay_addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+
+    // The relay lives as long as the test process, its connections end with the server.
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            while let Ok((mut client, _)) = listener.accept().await {
+                let connector = connector.clone();
+                tokio::spawn(async move {
+                    let stream = tokio::net::TcpStream::connect(server_addr).await?;
+                    let name = ServerName::try_from("localhost").unwrap();
+                    let mut stream = connector.connect(name, stream).await?;
+                    tokio::io::copy_bidirectional(&mut client, &mut stream).await
+                });
+            }
+        });
+    });
+
+    format!("ws://{relay_addr}")
+}
+
+#[test]
+fn ones() {
+    let server = TestServer::start();
+    let device = server.device();
+
+    let a: Tensor<B, 2> = Tensor::ones([3, 3], &device);
+
+    assert_eq!(a.dims(), [3, 3]);
+    assert_eq!(values(a), vec![1.0; 9]);
+}
+
+#[test]
+fn random() {
+    let server = TestServer::start();
+    let device = server.device();
+
+    let b: Tensor<B, 2> = Tensor::random([3, 3], Distribution::Uniform(-1.0, 1.0), &device);
+
+    assert_eq!(b.dims(), [3, 3]);
+    let values = values(b);
+    assert!(
+        values.iter().all(|value| (-1.0..1.0).contains(value)),
+        "{values:?} should be in [-1, 1)"
+    );
+}
+
+#[test]
+fn add() {
+    let server = TestServer::start();
+    let device = server.device();
+
+    let a: Tensor<B, 2> = Tensor::ones([3, 3], &device);
+    let b: Tensor<B, 2> = Tensor::random([3, 3], Distribution::Uniform(-1.0, 1.0), &device);
+    let c = a + b.clone();
+
+    let expected: Vec<f32> = values(b).iter().map(|value| value + 1.0).collect();
+    assert_approx_eq(&values(c), &expected);
+}
+
+#[test]
+fn matmul() {
+    let server = TestServer::start();
+    let device = server.device();
+
+    let a: Tensor<B, 2> = Tensor::ones([3, 3], &device);
+    let b: Tensor<B, 2> = Tensor::random([3, 3], Distribution::Uniform(-1.0, 1.0), &device);
+    let d = a.matmul(b.clone());
+
+    // Each row of a matrix of ones times B holds the sums of the columns of B.
+    let b = values(b);
+    let column_sums: Vec<f32> = (0..3)
+        .map(|column| (0..3).map(|row| b[row * 3 + column]).sum())
+        .collect();
+    let expected = column_sums.repeat(3);
+    assert_approx_eq(&values(d), &expected);
+}
+
+#[test]
+fn device_route() {
+    let server = TestServer::start_with(|config| config.devices = Some(Devices::All));
+    let device = RemoteDevice::new(&format!("{}/device/0", server.url()));
+
+    let a: Tensor<B, 2> = Tensor::ones([3, 3], &device);
+    let b = a.clone() + a;
+
+    assert_eq!(values(b), vec![2.0; 9]);
+}
+
+#[test]
+fn health() {
+    let server = TestServer::start();
+    let config = ServerConfig {
+        port: server.handle().local_addr().port(),
+        ..TestServer::config()
+    };
+
+    for endpoint in [HealthEndpoint::Alive, HealthEndpoint::Ready] {
+        check_health(&config, endpoint, Duration::from_secs(5))
+            .unwrap_or_else(|err| panic!("{endpoint} should succeed: {err}"));
+    }
+    let response = http(
+        &server,
+        "GET /device/5/readyz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    );
+    assert!(response.starts_with("HTTP/1.1 404"), "{response}");
+}
+
+#[test]
+fn health_of_a_stopped_server() {
+    // A port that nothing listens on anymore.
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .unwrap()
+        .port();
+    let config = ServerConfig {
+        port,
+        ..TestServer::config()
+    };
+
+    let result = check_health(&config, HealthEndpoint::Alive, Duration::from_secs(5));
+
+    assert!(
+        matches!(result, Err(ServerError::Unhealthy { .. })),
+        "{result:?}"
+    );
+}
+
+#[test]
+fn tls() {
+    let dir = tempfile::tempdir().unwrap();
+    let tls = self_signed(dir.path(), "server");
+    let server = TestServer::start_with(|config| config.tls = Some(tls.clone()));
+    assert!(server.url().starts_with("wss://"), "{}", server.url());
+
+    let device = RemoteDevice::new(&tls_relay(&server, &tls));
+    let a: Tensor<B, 2> = Tensor::ones([3, 3], &device);
+    assert_eq!(values(a.clone() + a), vec![2.0; 9]);
+
+    let mut config = ServerConfig {
+        port: server.handle().local_addr().port(),
+        tls: Some(tls),
+        ..TestServer::config()
+    };
+    check_health(&config, HealthEndpoint::Ready, Duration::from_secs(5))
+        .expect("the health check should trust the server certificate");
+    // The health check only trusts the configured certificate.
+    config.tls = Some(self_signed(dir.path(), "other"));
+    let result = check_health(&config, HealthEndpoint::Ready, Duration::from_secs(5));
+    assert!(
+        matches!(result, Err(ServerError::Unhealthy { .. })),
+        "{result:?}"
+    );
+}
+
+#[test]
+fn port_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("burn-server.port");
+    let server = TestServer::start_with(|config| config.port_file = Some(path.clone()));
+
+    let port = std::fs::read_to_string(&path).expect("the port file should be written");
+    assert_eq!(port.trim(), server.handle().local_addr().port().to_string());
+    // The port is written to a temporary file renamed over the port file.
+    assert!(!dir.path().join("burn-server.port.tmp").exists());
+}
+
+#[test]
+fn shutdown_closes_open_connections_after_the_timeout() {
+    let timeout = Duration::from_millis(500);
+    let server = TestServer::start_with(|config| config.shutdown_timeout = timeout);
+    let (mut socket, _) = tungstenite::connect(format!("{}/request", server.url()))
+        .expect("the server should accept the connection");
+    if let MaybeTlsStream::Plain(stream) = socket.get_mut() {
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+    }
+
+    let started = Instant::now();
+    server.handle().shutdown();
+    let message = socket
+        .read()
+        .expect("the server should close the connection");
+    drop(server);
+
+    let Message::Close(Some(frame)) = message else {
+        panic!("expected a close frame, got {message:?}");
+    };
+    assert_eq!(frame.code, CloseCode::Away);
+    let elapsed = started.elapsed();
+    assert!(elapsed >= timeout, "closed after {elapsed:?}");
+    assert!(
+        elapsed < timeout + Duration::from_secs(5),
+        "stopped after {elapsed:?}"
+    );
+}
+
+#[test]
+fn unversioned_clients_are_rejected_unless_allowed() {
+    let handshake = |server: &TestServer, headers: &str| {
+        http(
+            server,
+            &format!(
+                "GET /request HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+             Sec-WebSocket-Version: 13\r\n{headers}\r\n"
+            ),
+        )
+    };
+    let versioned = "x-burn-version: 0.21.0\r\nx-burn-protocol-revision: 1\r\n";
+
+    let server = TestServer::start_with(|config| config.allow_unversioned_clients = false);
+    let rejected = handshake(&server, "");
+    assert!(rejected.starts_with("HTTP/1.1 400"), "{rejected}");
+    let accepted = handshake(&server, versioned);
+    assert!(accepted.starts_with("HTTP/1.1 101"), "{accepted}");
+
+    let server = TestServer::start();
+    let accepted = handshake(&server, "");
+    assert!(accepted.starts_with("HTTP/1.1 101"), "{accepted}");
+}
+
+#[test]
+fn reload() {
+    let server = TestServer::start();
+    server.handle().reload_from(|| {
+        let mut config = TestServer::config();
+        config.port = 1;
+        config.limits.max_sessions = Some(1);
+        Ok(config)
+    });
+
+    let report = server
+        .handle()
+        .reload()
+        .expect("the configuration should reload");
+    assert_eq!(report.applied, ["max_sessions"]);
+    assert_eq!(report.restart_required, ["port"]);
+
+    // The client keeps its session open for the lifetime of the process.
+    let device = server.device();
+    let a: Tensor<B, 2> = Tensor::ones([3, 3], &device);
+    let b = a.clone() + a;
+    assert_eq!(values(b), vec![2.0; 9]);
+
+    // Another address of the server makes another client, whose session is rejected.
+    let other = RemoteDevice::new(&server.url().replace("127.0.0.1", "localhost"));
+    std::thread::spawn(move || {
+        let _ = std::panic::catch_unwind(|| {
+            Tensor::<B, 2>::ones([1, 1], &other).into_data();
+        });
+    });
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let metrics = metrics(&server);
+        if metrics.contains("\nburn_server_sessions_rejected_total 1") {
+            assert!(
+                metrics.contains("\nburn_server_sessions_active 1"),
+                "{metrics}"
+            );
+            break;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "the second session should be rejected:\n{metrics}"
+        );
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn reload_keeps_a_token_set_in_code() {
+    let server = TestServer::start_with(|config| config.token = Token::new("s3cret"));
+
+    let report = server
+        .handle()
+        .reload()
+        .expect("the configuration should reload");
+    assert_eq!(report, ReloadReport::default());
+
+    let handshake = |authorization: &str| {
+        http(
+            &server,
+            &format!(
+                "GET /request HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+             Sec-WebSocket-Version: 13\r\n{authorization}\r\n"
+            ),
+        )
+    };
+    let accepted = handshake("Authorization: Bearer s3cret\r\n");
+    assert!(accepted.starts_with("HTTP/1.1 101"), "{accepted}");
+    let rejected = handshake("");
+    assert!(rejected.starts_with("HTTP/1.1 401"), "{rejected}");
+
+    // Without a configuration file nor a source, there is nothing to reload over HTTP.
+    let reload = http(
+        &server,
+        "POST /admin/reload HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer s3cret\r\n\
+         Content-Length: 0\r\nConnection: close\r\n\r\n",
+    );
+    assert!(reload.starts_with("HTTP/1.1 404"), "{reload}");
+}
+
+#[test]
+fn placed_session() {
+    let server = TestServer::start_with(|config| {
+        config.devices = Some(Devices::All);
+        config.placement = Placement::MemoryInUse;
+    });
+    let device = RemoteDevice::new(&server.url());
+
+    let a: Tensor<B, 2> = Tensor::ones([3, 3], &device);
+    let b = a.clone() + a;
+
+    assert_eq!(values(b), vec![2.0; 9]);
+}
@@ -0,0 +1,37 @@
+//! Records the version of burn the client is built with, read from the lock file, so that the
+//! proxy can report it to the server.
+
+use std::path::PathBuf;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let manifest_dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    // The lock file is next to the manifest, or at the root of the workspace.
+    let lock = manifest_dir
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.is_file());
+
+    let version = lock
+        .and_then(|path| {
+            println!("cargo:rerun-if-changed={}", path.display());
+            std::fs::read_to_string(path).ok()
+        })
+        .and_then(|lock| burn_version(&lock))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=BURN_VERSION={version}");
+}
+
+/// The version of the `burn` package in a lock file.
+fn burn_version(lock: &str) -> Option<String> {
+    let mut lines = lock.lines();
+    lines.find(|line| *line == r#"name = "burn""#)?;
+
+    lines
+        .next()?
+        .strip_prefix(r#"version = ""#)?
+        .strip_suffix('"')
+        .map(str::to_string)
+}
@@ -0,0 +1,45 @@
+//! Token authentication for the remote backend.
+
+use std::io;
+
+/// The token to present to the server, from `REMOTE_BACKEND_TOKEN` or the first token of the
+/// file at `REMOTE_BACKEND_TOKEN_FILE`.
+///
+/// Surrounding whitespace is trimmed, and tokens with control characters, which would end up in
+/// the headers of the handshake, are rejected.
+pub fn token_from_env() -> io::Result<Option<String>> {
+    if let Ok(token) = std::env::var("REMOTE_BACKEND_TOKEN") {
+        if !token.trim().is_empty() {
+            return checked(token.trim(), "REMOTE_BACKEND_TOKEN").map(Some);
+        }
+    }
+
+    let Some(path) = std::env::var_os("REMOTE_BACKEND_TOKEN_FILE") else {
+        return Ok(None);
+    };
+    let content = std::fs::read_to_string(&path)?;
+    let token = content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("no token in {}", path.to_string_lossy()),
+            )
+        })?;
+
+    checked(token, &path.to_string_lossy()).map(Some)
+}
+
+/// The token, unless it has control characters, e.g. a carriage return left by an editor.
+fn checked(token: &str, source: &str) -> io::Result<String> {
+    if token.chars().any(char::is_control) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the token in {source} contains control characters"),
+        ));
+    }
+
+    Ok(token.to_string())
+}
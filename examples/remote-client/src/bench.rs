@@ -0,0 +1,280 @@
+//! Measures what the remote device is worth: the round-trip latency of a trivial operation, the
+//! throughput of uploads and downloads of large tensors, and the matmul throughput at several
+//! sizes.
+
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use burn::backend::remote::RemoteDevice;
+use burn::tensor::backend::Backend as _;
+use burn::tensor::{Distribution, Tensor, TensorData};
+use serde::Serialize;
+
+use crate::Backend;
+
+const MIB: usize = 1024 * 1024;
+
+#[derive(clap::Args)]
+pub struct BenchArgs {
+    /// Write the results as JSON to this file, to compare servers or runs
+    #[arg(long, value_name = "PATH")]
+    json: Option<PathBuf>,
+
+    /// Round trips measured for the latency
+    #[arg(long, default_value_t = 100, value_name = "N")]
+    latency_iterations: usize,
+
+    /// Sizes of the uploaded and downloaded tensors, in MiB, at most the maximum WebSocket frame
+    /// size of the server, 16 MiB by default
+    #[arg(long, value_delimiter = ',', default_values_t = [1, 4, 12], value_name = "MIB")]
+    transfer_sizes: Vec<usize>,
+
+    /// Transfers measured at each size
+    #[arg(long, default_value_t = 5, value_name = "N")]
+    transfer_iterations: usize,
+
+    /// Sizes of the square matrices multiplied
+    #[arg(long, value_delimiter = ',', default_values_t = [256, 512, 1024, 2048], value_name = "N")]
+    matmul_sizes: Vec<usize>,
+
+    /// Multiplications measured at each size
+    #[arg(long, default_value_t = 10, value_name = "N")]
+    matmul_iterations: usize,
+}
+
+#[derive(Serialize)]
+struct Report {
+    url: String,
+    server: Option<String>,
+    /// The device the server placed the session on, when it serves several devices.
+    device: Option<String>,
+    /// Whether every benchmark ran, false when the server closed the connection midway.
+    complete: bool,
+    latency: Option<Latency>,
+    transfers: Vec<Transfer>,
+    matmul: Vec<Matmul>,
+}
+
+/// The results measured so far, which can be written when the server closes the connection
+/// before the benchmarks are done.
+pub struct Results {
+    json: Option<PathBuf>,
+    report: Mutex<Report>,
+}
+
+impl Results {
+    pub fn new(url: &str, args: &BenchArgs) -> Self {
+        Self {
+            json: args.json.clone(),
+            report: Mutex::new(Report {
+                url: url.to_string(),
+                server: None,
+                device: None,
+                complete: false,
+                latency: None,
+                transfers: Vec::new(),
+                matmul: Vec::new(),
+            }),
+        }
+    }
+
+    /// Write the results measured so far as JSON when requested.
+    pub fn write(&self) {
+        let Some(path) = &self.json else {
+            return;
+        };
+        let mut report = self.report();
+        report.server = crate::proxy::server().map(str::to_string);
+        report.device = crate::proxy::device().map(str::to_string);
+
+        let json = serde_json::to_string_pretty(&*report).expect("The report is serializable");
+        if let Err(err) = std::fs::write(path, json + "\n") {
+            eprintln!("Can't write the results to {}: {err}", path.display());
+            std::process::exit(1);
+        }
+        if report.complete {
+            println!("\nResults written to {}", path.display());
+        } else {
+            println!("\nPartial results written to {}", path.display());
+        }
+    }
+
+    fn report(&self) -> MutexGuard<'_, Report> {
+        self.report.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Round trips of the addition of one-element tensors read back by the client.
+#[derive(Serialize)]
+struct Latency {
+    iterations: usize,
+    min_ms: f64,
+    median_ms: f64,
+    p95_ms: f64,
+    max_ms: f64,
+}
+
+/// The median throughput of the transfers of a tensor of `f32`.
+#[derive(Serialize)]
+struct Transfer {
+    bytes: usize,
+    upload_mib_per_s: f64,
+    download_mib_per_s: f64,
+}
+
+/// The throughput of multiplications of square matrices of `f32`, queued back to back.
+#[derive(Serialize)]
+struct Matmul {
+    size: usize,
+    iterations: usize,
+    seconds: f64,
+    gflop_per_s: f64,
+}
+
+/// Run the benchmarks on the device, print the results, record them and write them as JSON when
+/// requested.
+pub fn run(device: &RemoteDevice, args: BenchArgs, results: &Results) {
+    // Connects, and shows that the server computes before anything is measured.
+    sync(device);
+
+    println!("\n--- Round-trip latency ---");
+    let latency = latency(device, args.latency_iterations);
+    println!(
+        "{} iterations: min {:.3} ms, median {:.3} ms, p95 {:.3} ms, max {:.3} ms",
+        latency.iterations, latency.min_ms, latency.median_ms, latency.p95_ms, latency.max_ms
+    );
+    results.report().latency = Some(latency);
+
+    println!("\n--- Transfer throughput ---");
+    println!("{:>10} {:>16} {:>16}", "size", "upload", "download");
+    for &mib in &args.transfer_sizes {
+        let transfer = transfer(device, mib * MIB, args.transfer_iterations);
+        println!(
+            "{:>6} MiB {:>10.1} MiB/s {:>10.1} MiB/s",
+            mib, transfer.upload_mib_per_s, transfer.download_mib_per_s
+        );
+        results.report().transfers.push(transfer);
+    }
+
+    println!("\n--- Matmul throughput ---");
+    println!(
+        "{:>11} {:>10} {:>10} {:>10}",
+        "size", "iterations", "seconds", "GFLOP/s"
+    );
+    for &size in &args.matmul_sizes {
+        let matmul = matmul(device, size, args.matmul_iterations);
+        println!(
+            "{:>11} {:>10} {:>10.3} {:>10.1}",
+            format!("{size}x{size}"),
+            matmul.iterations,
+            matmul.seconds,
+            matmul.gflop_per_s
+        );
+        results.report().matmul.push(matmul);
+    }
+
+    results.report().complete = true;
+    results.write();
+}
+
+fn latency(device: &RemoteDevice, iterations: usize) -> Latency {
+    let tensor: Tensor<Backend, 1> = Tensor::zeros([1], device);
+    let round_trip = || {
+        let start = Instant::now();
+        read(tensor.clone() + tensor.clone());
+        start.elapsed()
+    };
+
+    for _ in 0..iterations.min(10) {
+        round_trip();
+    }
+    let mut samples = (0..iterations.max(1))
+        .map(|_| round_trip())
+        .collect::<Vec<_>>();
+    samples.sort();
+
+    let millis = |duration: Duration| duration.as_secs_f64() * 1e3;
+    let percentile = |p: usize| millis(samples[(samples.len() - 1) * p / 100]);
+    Latency {
+        iterations: samples.len(),
+        min_ms: percentile(0),
+        median_ms: percentile(50),
+        p95_ms: percentile(95),
+        max_ms: percentile(100),
+    }
+}
+
+fn transfer(device: &RemoteDevice, bytes: usize, iterations: usize) -> Transfer {
+    let elements = bytes / size_of::<f32>();
+    let data = TensorData::new(vec![1.0f32; elements], [elements]);
+    let iterations = iterations.max(1);
+
+    let mut uploads = Vec::with_capacity(iterations);
+    let mut downloads = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let tensor: Tensor<Backend, 1> = Tensor::from_data(data.clone(), device);
+        sync(device);
+        uploads.push(start.elapsed());
+
+        let start = Instant::now();
+        read(tensor);
+        downloads.push(start.elapsed());
+    }
+
+    let throughput = |mut samples: Vec<Duration>| {
+        samples.sort();
+        bytes as f64 / MIB as f64 / samples[samples.len() / 2].as_secs_f64()
+    };
+    Transfer {
+        bytes,
+        upload_mib_per_s: throughput(uploads),
+        download_mib_per_s: throughput(downloads),
+    }
+}
+
+fn matmul(device: &RemoteDevice, size: usize, iterations: usize) -> Matmul {
+    let distribution = Distribution::Uniform(-1.0, 1.0);
+    let a: Tensor<Backend, 2> = Tensor::random([size, size], distribution, device);
+    let b: Tensor<Backend, 2> = Tensor::random([size, size], distribution, device);
+    let iterations = iterations.max(1);
+
+    // Compiles the kernels and allocates the memory of this size.
+    let warmup = a.clone().matmul(b.clone());
+    sync(device);
+    drop(warmup);
+
+    // The products are kept until the device is synchronized, so that none can be skipped.
+    let start = Instant::now();
+    let products = (0..iterations)
+        .map(|_| a.clone().matmul(b.clone()))
+        .collect::<Vec<_>>();
+    sync(device);
+    let seconds = start.elapsed().as_secs_f64();
+    drop(products);
+
+    let flop = 2.0 * (size as f64).powi(3) * iterations as f64;
+    Matmul {
+        size,
+        iterations,
+        seconds,
+        gflop_per_s: flop / seconds / 1e9,
+    }
+}
+
+/// Wait for the operations queued on the device, exiting with the error when they failed.
+fn sync(device: &RemoteDevice) {
+    if let Err(err) = Backend::sync(device) {
+        eprintln!("The server failed to execute the benchmark: {err}");
+        std::process::exit(1);
+    }
+}
+
+/// Read the tensor back, exiting with the error when the server failed to compute it.
+fn read<const D: usize>(tensor: Tensor<Backend, D>) -> TensorData {
+    tensor.try_into_data().unwrap_or_else(|err| {
+        eprintln!("The server failed to execute the benchmark: {err}");
+        std::process::exit(1);
+    })
+}
@@ -15,46 +15,162 @@
 //!    export REMOTE_BACKEND_URL=ws://your-server-ip:3000
 //!    ```
 //!
+//!    If the server requires a token, also set REMOTE_BACKEND_TOKEN (or REMOTE_BACKEND_TOKEN_FILE
+//!    to read it from a file):
+//!    ```bash
+//!    export REMOTE_BACKEND_TOKEN=your-token
+//!    ```
+//!
+//!    For a server serving TLS, use a `wss://` URL. Its certificate is verified against the
+//!    Mozilla root certificates, or against the PEM bundle at REMOTE_BACKEND_CA_BUNDLE, e.g. the
+//!    certificate itself when it is self-signed:
+//!    ```bash
+//!    export REMOTE_BACKEND_URL=wss://your-server-name:3000
+//!    export REMOTE_BACKEND_CA_BUNDLE=/path/to/cert.pem
+//!    ```
+//!
 //! 3. Run this client:
 //!    ```bash
 //!    cargo run --release
 //!    ```
+//!
+//! # Benchmark
+//!
+//! The `bench` subcommand measures the round-trip latency of a trivial operation, the upload and
+//! download throughput of large tensors and the matmul throughput at several sizes, printing a
+//! table, and writing the results as JSON to compare servers:
+//! ```bash
+//! cargo run --release -- bench --json results.json
+//! ```
+//!
+//! When the server refuses or closes the connection, the client prints the reason, writes the
+//! results measured so far and exits with 1, or with 0 when the server is shutting down.
+
+mod auth;
+mod bench;
+mod proxy;
+mod tls;
 
+use std::panic::AssertUnwindSafe;
+use std::sync::{mpsc, Arc};
+
+use burn::backend::remote::RemoteDevice;
 use burn::backend::RemoteBackend;
 use burn::tensor::Tensor;
+use clap::{Parser, Subcommand};
 
 type Backend = RemoteBackend;
 
+#[derive(Parser)]
+#[command(about = "Example client for connecting to Burn Remote Backend Server")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Measure the latency, the transfer throughput and the matmul throughput of the server
+    Bench(bench::BenchArgs),
+}
+
+/// What the main thread waits for.
+enum Event {
+    /// The operations are done, `false` when they panicked.
+    Finished(bool),
+    /// The server refused or closed the connection.
+    Closed(proxy::Closed),
+}
+
 fn main() {
+    let cli = Cli::parse();
+
     let url =
         std::env::var("REMOTE_BACKEND_URL").unwrap_or_else(|_| "ws://localhost:3000".to_string());
 
     println!("Connecting to Burn Remote Backend at {}...", url);
 
+    let (events, received) = mpsc::channel();
+
+    // Connect through a local proxy that presents the token, speaks TLS to wss:// servers and
+    // reports why the server refused or closed the connection, e.g. when it is at capacity
+    let token = auth::token_from_env().expect("Failed to read the token");
+    let on_close = {
+        let events = events.clone();
+        move |closed| {
+            let _ = events.send(Event::Closed(closed));
+        }
+    };
+    let proxy_url =
+        proxy::start(&url, token.as_deref(), on_close).expect("Failed to start the proxy");
+
     // The remote device connects to the WebSocket server
-    let device = burn::backend::remote::RemoteDevice::new(&url);
+    let device = RemoteDevice::new(&proxy_url);
 
+    let bench = cli.command.map(|Command::Bench(args)| {
+        let results = Arc::new(bench::Results::new(&url, &args));
+        (args, results)
+    });
+    let results = bench.as_ref().map(|(_, results)| results.clone());
+
+    // The remote device waits forever once the server closed its connection, so the operations
+    // run on their own thread while this one waits for them or for the proxy to report the close
+    std::thread::spawn(move || {
+        let finished = std::panic::catch_unwind(AssertUnwindSafe(|| match bench {
+            Some((args, results)) => bench::run(&device, args, &results),
+            None => demo(&device),
+        }));
+        let _ = events.send(Event::Finished(finished.is_ok()));
+    });
+
+    match received.recv().expect("The proxy keeps a sender") {
+        Event::Finished(true) => {}
+        Event::Finished(false) => std::process::exit(101),
+        Event::Closed(closed) => {
+            eprintln!("{closed}");
+            if let Some(results) = &results {
+                results.write();
+            }
+            std::process::exit(if closed.is_shutdown() { 0 } else { 1 });
+        }
+    }
+}
+
+/// Run a few operations on the remote device and print their results.
+fn demo(device: &RemoteDevice) {
     // Create tensors - these operations run on the remote GPU!
     println!("\n--- Creating tensors on remote GPU ---");
 
-    let a: Tensor<Backend, 2> = Tensor::ones([3, 3], &device);
-    println!("Tensor A (ones 3x3):\n{:?}", a);
+    let a: Tensor<Backend, 2> = Tensor::ones([3, 3], device);
+    print("Tensor A (ones 3x3)", &a);
 
     let b: Tensor<Backend, 2> = Tensor::random(
         [3, 3],
         burn::tensor::Distribution::Uniform(-1.0, 1.0),
-        &device,
+        device,
     );
-    println!("Tensor B (random 3x3):\n{:?}", b);
+    print("Tensor B (random 3x3)", &b);
 
     // Matrix operations
     println!("\n--- Matrix operations on remote GPU ---");
 
     let c = a.clone() + b.clone();
-    println!("A + B:\n{:?}", c);
+    print("A + B", &c);
 
     let d = a.matmul(b);
-    println!("A @ B (matmul):\n{:?}", d);
+    print("A @ B (matmul)", &d);
 
     println!("\nRemote GPU operations completed successfully!");
 }
+
+/// Read the tensor back from the server and print it, exiting with the error when the server
+/// failed to compute it, e.g. when the session exceeded its memory quota.
+fn print(label: &str, tensor: &Tensor<Backend, 2>) {
+    match tensor.clone().try_into_data() {
+        Ok(_) => println!("{label}:\n{tensor}"),
+        Err(err) => {
+            eprintln!("{label}: {err}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,466 @@
+//! Local proxy to the remote backend.
+//!
+//! Burn's remote client only connects to plain `ws://` URLs, has no way to set
+//! headers on the WebSocket handshake and ignores the reason the server gives when it refuses or
+//! closes a connection. The client connects through a small local proxy instead, which adds the
+//! burn version and protocol revision of the client, plus an `Authorization: Bearer <token>`
+//! header when a token is set, to each handshake, connects to the server over TLS when needed, and
+//! then forwards the connection untouched.
+//!
+//! The versions the server answers with are printed on the first connection, and so is the device
+//! the server placed the session on when it serves several devices. The client can't
+//! recover from a connection refused or closed by the server, e.g. when its burn version is
+//! incompatible or the server is at capacity, and waits for its responses forever, so the proxy
+//! reports [why](Closed) to the caller, which decides how the process ends.
+
+use std::fmt;
+use std::io;
+use std::net::TcpListener as StdTcpListener;
+use std::sync::{Arc, OnceLock};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::TlsConnector;
+
+/// Maximum size of a handshake request or response, larger ones are dropped.
+const MAX_HANDSHAKE_SIZE: usize = 16 * 1024;
+
+/// Version of burn the client is built with, read from `Cargo.lock` by the build script.
+const BURN_VERSION: &str = env!("BURN_VERSION");
+
+/// Revision of the burn-server protocol spoken by the remote client of burn.
+const PROTOCOL_REVISION: u32 = 1;
+
+/// Why the server ended a connection the client can't recover from.
+#[derive(Debug)]
+pub enum Closed {
+    /// The server refused the handshake with this HTTP status and body.
+    Refused { status: String, body: String },
+    /// The server closed the connection with this close code and reason.
+    Close { code: u16, reason: String },
+}
+
+impl Closed {
+    /// Whether the server closed the connection because it is shutting down.
+    pub fn is_shutdown(&self) -> bool {
+        matches!(self, Closed::Close { code, .. } if *code == GOING_AWAY)
+    }
+}
+
+impl fmt::Display for Closed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Closed::Refused { status, body } => {
+                write!(
+                    f,
+                    "The server refused the connection (HTTP {status}): {body}"
+                )
+            }
+            Closed::Close { code, reason } => {
+                write!(f, "The server closed the connection ({code}): {reason}")
+            }
+        }
+    }
+}
+
+/// Called with the reason when the server refuses or closes a connection.
+type OnClose = Arc<dyn Fn(Closed) + Send + Sync>;
+
+/// The server the proxy forwards connections to.
+#[derive(Clone)]
+struct Upstream {
+    /// `host:port` of the server.
+    address: String,
+    /// TLS connector and server name to verify the certificate against, for `wss://` servers.
+    tls: Option<(TlsConnector, ServerName<'static>)>,
+    /// Header lines added to each handshake.
+    headers: String,
+    on_close: OnClose,
+}
+
+/// Start a proxy to the server at `url` (`ws://` or `wss://`), presenting the token on every
+/// connection when given, and calling `on_close` when the server refuses or closes a connection.
+///
+/// The certificate of `wss://` servers is verified against the CA bundle at
+/// `REMOTE_BACKEND_CA_BUNDLE`, see [`connector_from_env`](crate::tls::connector_from_env).
+///
+/// Returns the local `ws://` URL to connect the remote device to, with the path of `url`, e.g.
+/// `/device/1`. The proxy runs on a background thread for the lifetime of the process.
+pub fn start(
+    url: &str,
+    token: Option<&str>,
+    on_close: impl Fn(Closed) + Send + Sync + 'static,
+) -> io::Result<String> {
+    let invalid_url = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("expected a ws:// or wss:// URL, got {url}"),
+        )
+    };
+
+    let (address, secure) = match url.split_once("://") {
+        Some(("ws", address)) => (address, false),
+        Some(("wss", address)) => (address, true),
+        _ => return Err(invalid_url()),
+    };
+    let (address, path) = match address.split_once('/') {
+        Some((address, path)) => (address.to_string(), path.trim_end_matches('/')),
+        None => (address.to_string(), ""),
+    };
+
+    let tls = if secure {
+        let host = host(&address).ok_or_else(invalid_url)?;
+        let name = ServerName::try_from(host.to_string()).map_err(|_| invalid_url())?;
+        Some((crate::tls::connector_from_env()?, name))
+    } else {
+        None
+    };
+    let mut headers = format!(
+        "X-Burn-Version: {BURN_VERSION}\r\nX-Burn-Protocol-Revision: {PROTOCOL_REVISION}\r\n"
+    );
+    if let Some(token) = token {
+        headers.push_str(&format!("Authorization: Bearer {token}\r\n"));
+    }
+    let upstream = Upstream {
+        address,
+        tls,
+        headers,
+        on_close: Arc::new(on_close),
+    };
+
+    let listener = StdTcpListener::bind("127.0.0.1:0")?;
+    listener.set_nonblocking(true)?;
+    let local_url = match path {
+        "" => format!("ws://{}", listener.local_addr()?),
+        path => format!("ws://{}/{path}", listener.local_addr()?),
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()?;
+    std::thread::Builder::new()
+        .name("remote-proxy".to_string())
+        .spawn(move || {
+            if let Err(err) = runtime.block_on(serve(listener, upstream)) {
+                eprintln!("Proxy stopped: {err}");
+            }
+        })?;
+
+    Ok(local_url)
+}
+
+/// The host of a `host:port` address, without the brackets of IPv6 addresses.
+fn host(address: &str) -> Option<&str> {
+    let (host, _port) = address.rsplit_once(':')?;
+
+    Some(
+        host.strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(host),
+    )
+}
+
+/// Accept local connections and forward each of them to the server.
+async fn serve(listener: StdTcpListener, upstream: Upstream) -> io::Result<()> {
+    let listener = TcpListener::from_std(listener)?;
+
+    loop {
+        let (client, _) = listener.accept().await?;
+        let upstream = upstream.clone();
+        tokio::spawn(async move {
+            if let Err(err) = forward(client, &upstream).await {
+                eprintln!("Proxy connection to {} failed: {err}", upstream.address);
+            }
+        });
+    }
+}
+
+/// Forward a connection to the server, adding the headers to its handshake.
+async fn forward(mut client: TcpStream, upstream: &Upstream) -> io::Result<()> {
+    let mut request = Vec::with_capacity(1024);
+    let head_end = loop {
+        if let Some(end) = find_head_end(&request) {
+            break end;
+        }
+        if request.len() > MAX_HANDSHAKE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "handshake request too large",
+            ));
+        }
+        let mut buf = [0; 1024];
+        let read = client.read(&mut buf).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..read]);
+    };
+
+    // Insert the headers before the blank line ending the request head.
+    let insert_at = head_end - 2;
+    request.splice(insert_at..insert_at, upstream.headers.bytes());
+
+    let server = TcpStream::connect(&upstream.address).await?;
+    server.set_nodelay(true)?;
+    match &upstream.tls {
+        Some((connector, name)) => {
+            let server = connector.connect(name.clone(), server).await?;
+            relay(client, server, &request, &upstream.on_close).await
+        }
+        None => relay(client, server, &request, &upstream.on_close).await,
+    }
+}
+
+/// Send the handshake request to the server, check its response, then relay both directions
+/// until either side closes.
+async fn relay<S>(
+    client: TcpStream,
+    mut server: S,
+    request: &[u8],
+    on_close: &OnClose,
+) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    server.write_all(request).await?;
+
+    let (mut client_read, mut client_write) = tokio::io::split(client);
+    let (mut server_read, mut server_write) = tokio::io::split(server);
+
+    let response = match read_response(&mut server_read).await? {
+        Ok(response) => response,
+        Err(refused) => {
+            on_close(refused);
+            // The client panics on a refused connection, so it is kept waiting instead while the
+            // caller ends the process.
+            return std::future::pending().await;
+        }
+    };
+    client_write.write_all(&response).await?;
+
+    let upstream = async {
+        tokio::io::copy(&mut client_read, &mut server_write).await?;
+        server_write.shutdown().await
+    };
+    let downstream = async {
+        let mut frames = CloseFrameWatcher::default();
+        let mut buf = vec![0; 16 * 1024];
+        loop {
+            let read = server_read.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            if let Some((code, reason)) = frames.feed(&buf[..read]) {
+                if code != NORMAL_CLOSURE {
+                    on_close(Closed::Close { code, reason });
+                }
+            }
+            client_write.write_all(&buf[..read]).await?;
+        }
+        client_write.shutdown().await
+    };
+    tokio::try_join!(upstream, downstream)?;
+
+    Ok(())
+}
+
+/// The versions reported by the server on the first handshake.
+static SERVER: OnceLock<String> = OnceLock::new();
+
+/// The versions of the server, once connected to a server reporting them.
+pub fn server() -> Option<&'static str> {
+    SERVER.get().map(String::as_str)
+}
+
+/// The device the server placed the session on, when it serves several devices.
+static DEVICE: OnceLock<String> = OnceLock::new();
+
+/// The device running the session, once connected to a server serving several devices.
+pub fn device() -> Option<&'static str> {
+    DEVICE.get().map(String::as_str)
+}
+
+/// Read the response to the handshake, up to the end of its head, reporting the versions of the
+/// server and the device of the session the first time.
+///
+/// Returns the reason given by the server when it refused the connection.
+async fn read_response<R>(server: &mut R) -> io::Result<Result<Vec<u8>, Closed>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut response = Vec::with_capacity(1024);
+    let head_end = loop {
+        if let Some(end) = find_head_end(&response) {
+            break end;
+        }
+        if response.len() > MAX_HANDSHAKE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "handshake response too large",
+            ));
+        }
+        let mut buf = [0; 1024];
+        let read = server.read(&mut buf).await?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        response.extend_from_slice(&buf[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&response[..head_end]).into_owned();
+    let status = head.split_whitespace().nth(1).unwrap_or_default();
+    if status != "101" {
+        let mut body = response[head_end..].to_vec();
+        let length = header(&head, "content-length")
+            .and_then(|length| length.parse().ok())
+            .unwrap_or(0)
+            .min(MAX_HANDSHAKE_SIZE);
+        while body.len() < length {
+            let mut buf = [0; 1024];
+            let read = server.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            body.extend_from_slice(&buf[..read]);
+        }
+        return Ok(Err(Closed::Refused {
+            status: status.to_string(),
+            body: String::from_utf8_lossy(&body).trim().to_string(),
+        }));
+    }
+
+    let versions = (
+        header(&head, "x-burn-server-version"),
+        header(&head, "x-burn-version"),
+        header(&head, "x-burn-protocol-revision"),
+    );
+    if let (Some(server), Some(burn), Some(revision)) = versions {
+        let description =
+            format!("burn-server {server}, burn {burn}, protocol revision {revision}");
+        if SERVER.set(description.clone()).is_ok() {
+            println!("Server: {description}");
+        }
+    }
+    if let Some(device) = header(&head, "x-burn-server-device") {
+        if DEVICE.set(device.to_string()).is_ok() {
+            println!("Device: {device}");
+        }
+    }
+
+    Ok(Ok(response))
+}
+
+/// The value of a header in an HTTP response head, by case-insensitive name.
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (header, value) = line.split_once(':')?;
+        header
+            .trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim())
+    })
+}
+
+/// Close code of a connection closed normally.
+const NORMAL_CLOSURE: u16 = 1000;
+
+/// Close code of a connection closed by a server shutting down.
+const GOING_AWAY: u16 = 1001;
+
+/// Follows the WebSocket frames sent by the server after the handshake to find a close frame.
+///
+/// Only the header of each frame is parsed, the payload is skipped unless it is the one of a close
+/// frame.
+#[derive(Default)]
+struct CloseFrameWatcher {
+    /// Bytes of the header of the next frame read so far.
+    header: Vec<u8>,
+    /// Bytes left in the payload of the current frame.
+    remaining: u64,
+    /// Payload of the current frame when it is a close frame.
+    close: Option<Vec<u8>>,
+}
+
+impl CloseFrameWatcher {
+    /// Follow the next bytes sent by the server, returning the code and reason of the close frame
+    /// once it is complete.
+    fn feed(&mut self, mut data: &[u8]) -> Option<(u16, String)> {
+        while !data.is_empty() {
+            if self.remaining > 0 {
+                let len = data.len().min(self.remaining as usize);
+                if let Some(payload) = &mut self.close {
+                    payload.extend_from_slice(&data[..len]);
+                }
+                self.remaining -= len as u64;
+                data = &data[len..];
+            } else {
+                self.header.push(data[0]);
+                data = &data[1..];
+
+                let Some((opcode, len)) = parse_frame_header(&self.header) else {
+                    continue;
+                };
+                self.header.clear();
+                self.remaining = len;
+                if opcode == 0x8 {
+                    self.close = Some(Vec::new());
+                }
+            }
+
+            if self.remaining == 0 {
+                if let Some(payload) = self.close.take() {
+                    return Some(parse_close_payload(&payload));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// The opcode and payload length of a frame, once its header is complete.
+fn parse_frame_header(header: &[u8]) -> Option<(u8, u64)> {
+    let [first, second, ..] = *header else {
+        return None;
+    };
+    let (length_size, mask_size) = (
+        match second & 0x7f {
+            126 => 2,
+            127 => 8,
+            _ => 0,
+        },
+        if second & 0x80 != 0 { 4 } else { 0 },
+    );
+    if header.len() < 2 + length_size + mask_size {
+        return None;
+    }
+
+    let len = match length_size {
+        0 => u64::from(second & 0x7f),
+        _ => header[2..2 + length_size]
+            .iter()
+            .fold(0, |len, byte| (len << 8) | u64::from(*byte)),
+    };
+
+    Some((first & 0x0f, len))
+}
+
+/// The code and reason of a close frame, `1005` (no status) when it has none.
+fn parse_close_payload(payload: &[u8]) -> (u16, String) {
+    match payload {
+        [high, low, reason @ ..] => (
+            u16::from_be_bytes([*high, *low]),
+            String::from_utf8_lossy(reason).into_owned(),
+        ),
+        _ => (1005, String::new()),
+    }
+}
+
+/// The end of an HTTP head, just past its terminating blank line.
+fn find_head_end(request: &[u8]) -> Option<usize> {
+    request
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|position| position + 4)
+}
@@ -0,0 +1,46 @@
+//! TLS for `wss://` connections to the remote backend.
+
+use std::io;
+use std::sync::Arc;
+
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+/// A TLS connector trusting the certificates of the PEM bundle at `REMOTE_BACKEND_CA_BUNDLE`,
+/// e.g. a self-signed server certificate, or the Mozilla root certificates when it is not set.
+pub fn connector_from_env() -> io::Result<TlsConnector> {
+    let mut roots = RootCertStore::empty();
+
+    match std::env::var_os("REMOTE_BACKEND_CA_BUNDLE").filter(|path| !path.is_empty()) {
+        Some(path) => {
+            let certs = CertificateDer::pem_file_iter(&path)
+                .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                .map_err(|err| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("can't load {}: {err}", path.to_string_lossy()),
+                    )
+                })?;
+            let (added, _) = roots.add_parsable_certificates(certs);
+            if added == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("no certificate in {}", path.to_string_lossy()),
+                ));
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+
+    let config = ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(io::Error::other)?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+
+    Ok(TlsConnector::from(Arc::new(config)))
+}